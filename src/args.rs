@@ -0,0 +1,905 @@
+//! Parsing of the arguments accepted by the `jni` attribute.
+
+use proc_macro2::{Span, TokenStream};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+
+use crate::ident::{validate_name, NameKind};
+use crate::{descriptor, kotlin};
+
+/// Arguments accepted by the `jni` attribute.
+///
+/// The namespace is either given as a leading string, like `"com.example.Foo"`, or split up into
+/// `package = "com.example"` and `class = "Foo"` options. Every other option is written as
+/// `key = "value"`, except for the `relaxed`, `export_name` and `register` flags.
+///
+/// Namespaces may also depend on `cfg` predicates, as in
+/// `#[jni(cfg(debug_assertions) = "com.example.debug.Foo", default = "com.example.Foo")]`.
+#[derive(Clone, Default)]
+pub(crate) struct JniArgs {
+    /// The fully-qualified class name, when given as a leading string or as `default = "..."`.
+    pub(crate) namespace: Option<StrExpr>,
+    /// Fully-qualified class names which replace `namespace` when their `cfg` predicate holds; the
+    /// first matching predicate wins.
+    pub(crate) cfg_namespaces: Vec<(TokenStream, StrExpr)>,
+    /// The package containing `class`; the unnamed package is used if this is missing.
+    pub(crate) package: Option<StrExpr>,
+    /// The class name within `package`; nested classes may be written as `Outer.Inner`.
+    pub(crate) class: Option<StrExpr>,
+    /// The package and name of a Kotlin file, like `com.example.Utils`, whose file facade class
+    /// (`com.example.UtilsKt`) should be used as the namespace.
+    pub(crate) kotlin_file: Option<StrExpr>,
+    /// A Kotlin class, like `com.example.Foo`, whose companion object class
+    /// (`com.example.Foo$Companion`) should be used as the namespace.
+    pub(crate) kotlin_companion: Option<StrExpr>,
+    /// The Kotlin module name appended to the names of `internal` functions, like `app_release`.
+    pub(crate) kotlin_internal_module: Option<StrExpr>,
+    /// Additional fully-qualified class names to export the function under, given as
+    /// `alias = "..."`; this may be repeated.
+    pub(crate) aliases: Vec<StrExpr>,
+    /// The Java method name, if it differs from the name of the Rust function.
+    pub(crate) method: Option<syn::LitStr>,
+    /// Method descriptor for overloaded methods, like `"(ILjava/lang/String;)V"`.
+    pub(crate) signature: Option<syn::LitStr>,
+    /// The `RenameRule` applied to the Rust function name, unless `method` is given.
+    pub(crate) rename_all: Option<syn::LitStr>,
+    /// The path of a trait, like `"crate::JniType"`, which allows parameter and return types that
+    /// aren't known JNI types as long as they implement it.
+    pub(crate) type_bound: Option<syn::LitStr>,
+    /// Set by the bare `relaxed` flag: accepts class and method names which aren't Java
+    /// identifiers but which the JVM allows, like Kotlin or Scala backtick identifiers.
+    pub(crate) relaxed: bool,
+    /// Set by the bare `export_name` flag: exported functions keep a Rust-style name, and are
+    /// exported with `#[unsafe(export_name = "...")]` rather than named after their symbol.
+    pub(crate) export_name: bool,
+    /// Set by the bare `register` flag: the function isn't exported, but registered with
+    /// `RegisterNatives` by the library's `on_load` hook, under its `signature` or the method
+    /// descriptor derived from its types.
+    pub(crate) register: bool,
+}
+
+/// Rules for deriving the Java method name from the name of the Rust function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RenameRule {
+    /// Use the Rust function name as-is; this is the default.
+    Verbatim,
+    /// Convert `snake_case` to `camelCase`.
+    CamelCase,
+    /// Convert `snake_case` to `PascalCase`.
+    PascalCase,
+}
+
+impl RenameRule {
+    fn from_lit(lit: &syn::LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "verbatim" => Ok(RenameRule::Verbatim),
+            "camelCase" => Ok(RenameRule::CamelCase),
+            "PascalCase" => Ok(RenameRule::PascalCase),
+            other => Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "Unknown `rename_all` rule `{}`; expected `camelCase`, `PascalCase` or `verbatim`",
+                    other
+                ),
+            )),
+        }
+    }
+
+    /// Applies the rule to a function name. Leading underscores are kept, and every other
+    /// underscore starts a new capitalized word.
+    pub(crate) fn apply(self, name: &str) -> String {
+        if self == RenameRule::Verbatim {
+            return name.to_string();
+        }
+
+        let trimmed = name.trim_start_matches('_');
+        let mut renamed = name[..name.len() - trimmed.len()].to_string();
+        for (i, word) in trimmed.split('_').filter(|w| !w.is_empty()).enumerate() {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap();
+            if i == 0 && self == RenameRule::CamelCase {
+                renamed.extend(first.to_lowercase());
+            } else {
+                renamed.extend(first.to_uppercase());
+            }
+            renamed.push_str(chars.as_str());
+        }
+        renamed
+    }
+}
+
+/// A class name resolved from `JniArgs`, along with the span that errors about it should use.
+pub(crate) struct Namespace {
+    pub(crate) value: String,
+    pub(crate) span: Span,
+    /// The string literal the name was taken from verbatim, if any, so that errors can point
+    /// inside it.
+    pub(crate) literal: Option<syn::LitStr>,
+}
+
+/// A string option which is resolved at expansion time. Besides string literals, this accepts
+/// `env!("NAME")`, `option_env!("NAME").unwrap_or("default")` and `concat!(...)` of any of these,
+/// which allows namespaces to be set by build scripts or build flavors.
+#[derive(Clone)]
+pub(crate) struct StrExpr(syn::Expr);
+
+impl Parse for StrExpr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse().map(StrExpr)
+    }
+}
+
+impl StrExpr {
+    pub(crate) fn span(&self) -> Span {
+        self.0.span()
+    }
+
+    /// Returns the string literal this expression consists of, if it is a plain literal.
+    pub(crate) fn literal(&self) -> Option<&syn::LitStr> {
+        let mut expr = &self.0;
+        while let syn::Expr::Group(group) = expr {
+            expr = &group.expr;
+        }
+        match expr {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(lit),
+                ..
+            }) => Some(lit),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the expression, reading any environment variables it refers to.
+    pub(crate) fn value(&self) -> syn::Result<String> {
+        evaluate_str_expr(&self.0)
+    }
+
+    /// Evaluates the expression as a fully-qualified class name, which may also be written in the
+    /// JVM's internal form (`com/example/Foo`) or as a descriptor (`Lcom/example/Foo;`).
+    pub(crate) fn class_name(&self) -> syn::Result<String> {
+        descriptor::binary_class_name(&self.value()?).map_err(|reason| {
+            syn::Error::new(
+                self.span(),
+                format!(
+                    "Invalid class name supplied to `jni_fn` attribute: {}",
+                    reason
+                ),
+            )
+        })
+    }
+
+    /// Appends items to `tokens` which make rustc track the environment variables read by
+    /// `value`, so that the crate is rebuilt when they change.
+    fn track_env(&self, tokens: &mut TokenStream) {
+        track_env(&self.0, tokens)
+    }
+}
+
+fn evaluate_str_expr(expr: &syn::Expr) -> syn::Result<String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Ok(lit.value()),
+        syn::Expr::Group(group) => evaluate_str_expr(&group.expr),
+        syn::Expr::Macro(m) if is_macro(&m.mac, "env") => {
+            let args = macro_args(&m.mac)?;
+            let name = env_var_name(&m.mac, &args)?;
+            std::env::var(&name).map_err(|_| {
+                let message = match args.get(1) {
+                    Some(message) => evaluate_str_expr(message).unwrap_or_default(),
+                    None => format!("environment variable `{}` not defined at compile time", name),
+                };
+                syn::Error::new(m.span(), message)
+            })
+        }
+        syn::Expr::Macro(m) if is_macro(&m.mac, "concat") => macro_args(&m.mac)?
+            .iter()
+            .map(evaluate_str_expr)
+            .collect(),
+        syn::Expr::Macro(m) if is_macro(&m.mac, "option_env") => Err(syn::Error::new(
+            m.span(),
+            "`option_env!` needs a default here, as in `option_env!(\"NAME\").unwrap_or(\"default\")`",
+        )),
+        syn::Expr::MethodCall(call) if call.method == "unwrap_or" && call.args.len() == 1 => {
+            match &*call.receiver {
+                syn::Expr::Macro(m) if is_macro(&m.mac, "option_env") => {
+                    let name = env_var_name(&m.mac, &macro_args(&m.mac)?)?;
+                    match std::env::var(name) {
+                        Ok(value) => Ok(value),
+                        Err(_) => evaluate_str_expr(&call.args[0]),
+                    }
+                }
+                other => Err(unsupported_str_expr(other)),
+            }
+        }
+        other => Err(unsupported_str_expr(other)),
+    }
+}
+
+fn track_env(expr: &syn::Expr, tokens: &mut TokenStream) {
+    match expr {
+        syn::Expr::Group(group) => track_env(&group.expr, tokens),
+        syn::Expr::Macro(m) if is_macro(&m.mac, "env") => {
+            // an `env!` without a name is reported when the namespace is evaluated
+            if let Some(name) = macro_args(&m.mac).ok().as_deref().and_then(<[_]>::first) {
+                tokens.extend(quote::quote! { const _: &str = ::core::env!(#name); });
+            }
+        }
+        syn::Expr::Macro(m) if is_macro(&m.mac, "concat") => {
+            for arg in macro_args(&m.mac).into_iter().flatten() {
+                track_env(&arg, tokens);
+            }
+        }
+        syn::Expr::MethodCall(call) => {
+            if let syn::Expr::Macro(m) = &*call.receiver {
+                let args = macro_args(&m.mac).ok();
+                let name = args.as_deref().and_then(<[_]>::first);
+                if let (true, Some(name)) = (is_macro(&m.mac, "option_env"), name) {
+                    tokens.extend(quote::quote! {
+                        const _: ::core::option::Option<&str> = ::core::option_env!(#name);
+                    });
+                }
+            }
+            call.args.iter().for_each(|arg| track_env(arg, tokens));
+        }
+        _ => {}
+    }
+}
+
+fn unsupported_str_expr(expr: &syn::Expr) -> syn::Error {
+    syn::Error::new(
+        expr.span(),
+        "Expected a string literal, `env!`, `option_env!(..).unwrap_or(..)` or `concat!`",
+    )
+}
+
+fn is_macro(mac: &syn::Macro, name: &str) -> bool {
+    mac.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == name)
+}
+
+fn macro_args(mac: &syn::Macro) -> syn::Result<Vec<syn::Expr>> {
+    let args = mac.parse_body_with(Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated)?;
+    Ok(args.into_iter().collect())
+}
+
+/// Returns the variable name passed to `env!` or `option_env!`, which must be a string literal.
+fn env_var_name(mac: &syn::Macro, args: &[syn::Expr]) -> syn::Result<String> {
+    match args.first() {
+        Some(syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(name),
+            ..
+        })) => Ok(name.value()),
+        _ => Err(syn::Error::new(
+            mac.span(),
+            "Expected an environment variable name as a string literal",
+        )),
+    }
+}
+
+impl Parse for JniArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = JniArgs::default();
+
+        if input.peek(syn::LitStr) || (input.peek(syn::Ident) && input.peek2(syn::Token![!])) {
+            args.namespace = Some(input.parse()?);
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            match key.to_string().as_str() {
+                "relaxed" | "export_name" | "register" => {
+                    let flag = match key.to_string().as_str() {
+                        "relaxed" => &mut args.relaxed,
+                        "export_name" => &mut args.export_name,
+                        _ => &mut args.register,
+                    };
+                    if *flag {
+                        return Err(syn::Error::new(
+                            key.span(),
+                            format!("`{}` may only be specified once", key),
+                        ));
+                    }
+                    *flag = true;
+                }
+                "cfg" => {
+                    let predicate;
+                    syn::parenthesized!(predicate in input);
+                    let predicate: TokenStream = predicate.parse()?;
+                    input.parse::<syn::Token![=]>()?;
+                    args.cfg_namespaces.push((predicate, input.parse()?));
+                }
+                "alias" => {
+                    input.parse::<syn::Token![=]>()?;
+                    args.aliases.push(input.parse()?);
+                }
+                "default"
+                | "package"
+                | "class"
+                | "kotlin_file"
+                | "kotlin_companion"
+                | "kotlin_internal_module" => {
+                    let slot = match key.to_string().as_str() {
+                        "default" => &mut args.namespace,
+                        "package" => &mut args.package,
+                        "class" => &mut args.class,
+                        "kotlin_file" => &mut args.kotlin_file,
+                        "kotlin_companion" => &mut args.kotlin_companion,
+                        _ => &mut args.kotlin_internal_module,
+                    };
+                    if slot.is_some() {
+                        return Err(syn::Error::new(
+                            key.span(),
+                            format!("`{}` may only be specified once", key),
+                        ));
+                    }
+                    input.parse::<syn::Token![=]>()?;
+                    *slot = Some(input.parse()?);
+                }
+                _ => {
+                    let slot = match key.to_string().as_str() {
+                        "method" => &mut args.method,
+                        "signature" => &mut args.signature,
+                        "rename_all" => &mut args.rename_all,
+                        "type_bound" => &mut args.type_bound,
+                        _ => {
+                            return Err(syn::Error::new(
+                                key.span(),
+                                format!("Unknown `jni_fn` attribute option `{}`", key),
+                            ))
+                        }
+                    };
+                    if slot.is_some() {
+                        return Err(syn::Error::new(
+                            key.span(),
+                            format!("`{}` may only be specified once", key),
+                        ));
+                    }
+                    input.parse::<syn::Token![=]>()?;
+                    *slot = Some(input.parse()?);
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+impl JniArgs {
+    /// Combines the arguments of a `mod` or `impl` block with those given to one of its functions,
+    /// which take precedence. A function may replace the whole namespace, or only its `package`
+    /// or `class`; the container's aliases are only kept if the namespace isn't replaced.
+    pub(crate) fn with_overrides(&self, overrides: JniArgs) -> JniArgs {
+        let replaces_namespace = overrides.namespace.is_some()
+            || !overrides.cfg_namespaces.is_empty()
+            || overrides.kotlin_file.is_some()
+            || overrides.kotlin_companion.is_some();
+
+        let mut args = overrides;
+        if replaces_namespace {
+            // the function's own namespace options are used as they are
+        } else if args.package.is_some() || args.class.is_some() {
+            args.package = args.package.or_else(|| self.package.clone());
+            args.class = args.class.or_else(|| self.class.clone());
+        } else {
+            args.namespace = self.namespace.clone();
+            args.cfg_namespaces = self.cfg_namespaces.clone();
+            args.package = self.package.clone();
+            args.class = self.class.clone();
+            args.kotlin_file = self.kotlin_file.clone();
+            args.kotlin_companion = self.kotlin_companion.clone();
+            args.aliases.splice(0..0, self.aliases.iter().cloned());
+        }
+
+        if args.kotlin_internal_module.is_none() {
+            args.kotlin_internal_module = self.kotlin_internal_module.clone();
+        }
+        if args.rename_all.is_none() {
+            args.rename_all = self.rename_all.clone();
+        }
+        if args.type_bound.is_none() {
+            args.type_bound = self.type_bound.clone();
+        }
+        args.relaxed |= self.relaxed;
+        args.export_name |= self.export_name;
+        args.register |= self.register;
+        args
+    }
+
+    /// Splits the arguments into one set per `cfg`-dependent namespace, each paired with the `cfg`
+    /// predicate under which it applies. The predicates are mutually exclusive, and `None` is
+    /// returned as the only predicate when there are no `cfg` namespaces. A default namespace is
+    /// required with `cfg` namespaces, since the function would silently not be exported on targets
+    /// where none of the predicates hold otherwise.
+    pub(crate) fn cfg_variants(&self) -> syn::Result<Vec<(Option<TokenStream>, JniArgs)>> {
+        let Some((first, _)) = self.cfg_namespaces.first() else {
+            return Ok(vec![(None, self.clone())]);
+        };
+        if self.package.is_some() || self.class.is_some() {
+            return Err(syn::Error::new(
+                first.span(),
+                "`cfg` namespaces can't be combined with `package` or `class` options",
+            ));
+        }
+        if self.namespace.is_none() {
+            return Err(syn::Error::new(
+                first.span(),
+                "`cfg` namespaces need a `default = \"...\"` namespace for when none of their predicates hold",
+            ));
+        }
+
+        let mut variants = Vec::new();
+        let mut previous: Vec<&TokenStream> = Vec::new();
+        for (predicate, namespace) in &self.cfg_namespaces {
+            let condition = if previous.is_empty() {
+                predicate.clone()
+            } else {
+                quote::quote! { all(#predicate, not(any(#(#previous),*))) }
+            };
+            let args = JniArgs {
+                namespace: Some(namespace.clone()),
+                cfg_namespaces: Vec::new(),
+                ..self.clone()
+            };
+            variants.push((Some(condition), args));
+            previous.push(predicate);
+        }
+        if self.namespace.is_some() {
+            let args = JniArgs {
+                cfg_namespaces: Vec::new(),
+                ..self.clone()
+            };
+            variants.push((Some(quote::quote! { not(any(#(#previous),*)) }), args));
+        }
+        Ok(variants)
+    }
+
+    /// Returns items which make rustc track the environment variables that the namespace options
+    /// read; see `StrExpr`.
+    pub(crate) fn env_dependencies(&self) -> TokenStream {
+        let mut tokens = TokenStream::new();
+        let namespaces = self.cfg_namespaces.iter().map(|(_, namespace)| namespace);
+        for value in [&self.namespace, &self.package, &self.class]
+            .into_iter()
+            .flatten()
+            .chain(namespaces)
+            .chain(&self.aliases)
+            .chain(&self.kotlin_file)
+            .chain(&self.kotlin_companion)
+            .chain(&self.kotlin_internal_module)
+        {
+            value.track_env(&mut tokens);
+        }
+        tokens
+    }
+
+    /// Resolves the fully-qualified class name the function should be exported under. Nested
+    /// classes given as `class = "Outer.Inner"` are joined with `$`, as they are in the JVM.
+    pub(crate) fn namespace(&self) -> syn::Result<Namespace> {
+        if let Some(namespace) = self.kotlin_namespace()? {
+            return Ok(namespace);
+        }
+
+        match (&self.namespace, &self.package, &self.class) {
+            (Some(namespace), None, None) => Ok(Namespace {
+                value: namespace.class_name()?,
+                span: namespace.span(),
+                literal: namespace.literal().cloned(),
+            }),
+            (Some(namespace), _, _) => Err(syn::Error::new(
+                namespace.span(),
+                "The namespace literal can't be combined with `package` or `class` options",
+            )),
+            (None, package, Some(class)) => {
+                let class_name = class.value()?;
+                validate_name(
+                    &class_name,
+                    NameKind::Class,
+                    self.relaxed,
+                    "Invalid class name supplied to `jni_fn` attribute",
+                    class.span(),
+                    class.literal(),
+                )?;
+                let class_name = class_name.replace('.', "$");
+
+                let value = match package {
+                    Some(package) => {
+                        let package_name = package.value()?;
+                        validate_name(
+                            &package_name,
+                            NameKind::Package,
+                            self.relaxed,
+                            "Invalid package namespace supplied to `jni_fn` attribute",
+                            package.span(),
+                            package.literal(),
+                        )?;
+                        format!("{}.{}", package_name, class_name)
+                    }
+                    None => class_name,
+                };
+                Ok(Namespace {
+                    value,
+                    span: class.span(),
+                    literal: None,
+                })
+            }
+            (None, Some(package), None) => Err(syn::Error::new(
+                package.span(),
+                "The `package` option requires a `class` option as well",
+            )),
+            (None, None, None) => Err(syn::Error::new(
+                Span::call_site(),
+                "The `jni_fn` attribute must specify a namespace, either as a string literal or with a `class` option",
+            )),
+        }
+    }
+
+    /// Resolves the namespace given by `kotlin_file` or `kotlin_companion`, if any.
+    fn kotlin_namespace(&self) -> syn::Result<Option<Namespace>> {
+        let (option, value, class_name): (_, _, fn(&str) -> String) =
+            match (&self.kotlin_file, &self.kotlin_companion) {
+                (Some(file), None) => ("kotlin_file", file, kotlin::file_facade_class),
+                (None, Some(class)) => ("kotlin_companion", class, kotlin::companion_class),
+                (None, None) => return Ok(None),
+                (Some(_), Some(class)) => {
+                    return Err(syn::Error::new(
+                        class.span(),
+                        "`kotlin_file` and `kotlin_companion` can't be combined",
+                    ))
+                }
+            };
+
+        if self.namespace.is_some() || self.package.is_some() || self.class.is_some() {
+            return Err(syn::Error::new(
+                value.span(),
+                format!(
+                    "`{}` can't be combined with a namespace, `package` or `class` option",
+                    option
+                ),
+            ));
+        }
+
+        let value_span = value.span();
+        let class_name = class_name(&value.class_name()?);
+        validate_name(
+            &class_name,
+            NameKind::Class,
+            self.relaxed,
+            &format!("Invalid class name supplied as `{}`", option),
+            value_span,
+            None,
+        )?;
+        Ok(Some(Namespace {
+            value: class_name,
+            span: value_span,
+            literal: None,
+        }))
+    }
+
+    /// Resolves the Java method name, which is the name of the Rust function (transformed by any
+    /// `rename_all` rule) unless it has been overridden with `method = "..."`. Kotlin `internal`
+    /// functions have their module name appended.
+    pub(crate) fn method_name(&self, ident: &syn::Ident) -> syn::Result<String> {
+        let name = self.unmangled_method_name(ident)?;
+        match &self.kotlin_internal_module {
+            Some(module) => Ok(kotlin::internal_method_name(&name, &module.value()?)),
+            None => Ok(name),
+        }
+    }
+
+    fn unmangled_method_name(&self, ident: &syn::Ident) -> syn::Result<String> {
+        match &self.method {
+            Some(method) => {
+                let name = method.value();
+                validate_name(
+                    &name,
+                    NameKind::Method,
+                    self.relaxed,
+                    "Invalid method name supplied to `jni_fn` attribute",
+                    method.span(),
+                    Some(method),
+                )?;
+                Ok(name)
+            }
+            None => {
+                let rule = match &self.rename_all {
+                    Some(rule) => RenameRule::from_lit(rule)?,
+                    None => RenameRule::Verbatim,
+                };
+                let name = rule.apply(&ident.unraw().to_string());
+                validate_name(
+                    &name,
+                    NameKind::Method,
+                    self.relaxed,
+                    "Invalid method name derived from the function name (set `method = \"...\"` to override it)",
+                    ident.span(),
+                    None,
+                )?;
+                Ok(name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(tokens: proc_macro2::TokenStream) -> syn::Result<JniArgs> {
+        syn::parse2(tokens)
+    }
+
+    #[test]
+    fn test_namespace_literal() {
+        let args = parse(quote::quote! { "com.example.Foo" }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.Foo");
+    }
+
+    #[test]
+    fn test_package_and_class() {
+        let args = parse(quote::quote! { package = "com.example", class = "Foo" }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.Foo");
+
+        let args = parse(quote::quote! { class = "Outer.Inner", package = "com.example" }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.Outer$Inner");
+
+        let args = parse(quote::quote! { class = "Foo", }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "Foo");
+
+        let args = parse(quote::quote! { package = "com.example.record", class = "Foo" }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.record.Foo");
+
+        let args = parse(quote::quote! { package = "com.example", class = "var" }).unwrap();
+        assert!(args.namespace().is_err());
+
+        let args =
+            parse(quote::quote! { package = "com.example", class = "var", relaxed }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.var");
+    }
+
+    #[test]
+    fn test_method_name() {
+        let ident: syn::Ident = syn::parse_quote!(say_hello);
+        let args = parse(quote::quote! { "com.example.Foo" }).unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "say_hello");
+
+        let args = parse(quote::quote! { "com.example.Foo", method = "sayHello" }).unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "sayHello");
+
+        let args = parse(quote::quote! { "com.example.Foo", method = "say.hello" }).unwrap();
+        assert!(args.method_name(&ident).is_err());
+
+        let ident: syn::Ident = syn::parse_quote!(r#match);
+        let args = parse(quote::quote! { "com.example.Foo" }).unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "match");
+
+        let ident: syn::Ident = syn::parse_quote!(int);
+        assert!(args.method_name(&ident).is_err());
+
+        let args = parse(quote::quote! { "com.example.Foo", relaxed }).unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "int");
+
+        let args = parse(quote::quote! { "com.example.Foo", method = "class" }).unwrap();
+        assert!(args.method_name(&ident).is_err());
+    }
+
+    #[test]
+    fn test_rename_rule() {
+        assert_eq!(RenameRule::Verbatim.apply("say_hello"), "say_hello");
+        assert_eq!(RenameRule::CamelCase.apply("say_hello"), "sayHello");
+        assert_eq!(RenameRule::PascalCase.apply("say_hello"), "SayHello");
+        assert_eq!(RenameRule::CamelCase.apply("close"), "close");
+        assert_eq!(RenameRule::PascalCase.apply("close"), "Close");
+        assert_eq!(RenameRule::CamelCase.apply("get_url_2"), "getUrl2");
+        assert_eq!(RenameRule::CamelCase.apply("sayHello"), "sayHello");
+        assert_eq!(RenameRule::CamelCase.apply("_private_init"), "_privateInit");
+        assert_eq!(
+            RenameRule::CamelCase.apply("double__underscore_"),
+            "doubleUnderscore"
+        );
+        assert_eq!(RenameRule::PascalCase.apply("über_größe"), "ÜberGröße");
+    }
+
+    #[test]
+    fn test_rename_all() {
+        let ident: syn::Ident = syn::parse_quote!(say_hello);
+        let args = parse(quote::quote! { "com.example.Foo", rename_all = "camelCase" }).unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "sayHello");
+
+        let args = parse(quote::quote! {
+            "com.example.Foo", rename_all = "PascalCase", method = "say_hello"
+        })
+        .unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "say_hello");
+
+        let args = parse(quote::quote! { "com.example.Foo", rename_all = "kebab-case" }).unwrap();
+        assert!(args.method_name(&ident).is_err());
+    }
+
+    #[test]
+    fn test_env_namespace() {
+        std::env::set_var("JAVA_NATIVE_TEST_PACKAGE", "com.example.app.debug");
+        std::env::remove_var("JAVA_NATIVE_TEST_MISSING");
+
+        let args = parse(quote::quote! { env!("JAVA_NATIVE_TEST_PACKAGE") }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.app.debug");
+
+        let args = parse(quote::quote! {
+            concat!(env!("JAVA_NATIVE_TEST_PACKAGE"), ".Native")
+        })
+        .unwrap();
+        assert_eq!(
+            args.namespace().unwrap().value,
+            "com.example.app.debug.Native"
+        );
+
+        let args = parse(quote::quote! {
+            package = option_env!("JAVA_NATIVE_TEST_MISSING").unwrap_or("com.example.app"),
+            class = "Native",
+        })
+        .unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.app.Native");
+
+        let args = parse(quote::quote! {
+            option_env!("JAVA_NATIVE_TEST_PACKAGE").unwrap_or("com.example.app")
+        })
+        .unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.app.debug");
+
+        let args = parse(quote::quote! { env!("JAVA_NATIVE_TEST_MISSING") }).unwrap();
+        assert_eq!(
+            args.namespace().err().unwrap().to_string(),
+            "environment variable `JAVA_NATIVE_TEST_MISSING` not defined at compile time"
+        );
+
+        let args = parse(quote::quote! { option_env!("JAVA_NATIVE_TEST_MISSING") }).unwrap();
+        assert!(args.namespace().is_err());
+    }
+
+    #[test]
+    fn test_env_dependencies() {
+        let args = parse(quote::quote! {
+            package = concat!(env!("A"), option_env!("B").unwrap_or("c")),
+            class = "Native",
+        })
+        .unwrap();
+        assert_eq!(
+            args.env_dependencies().to_string(),
+            quote::quote! {
+                const _: &str = ::core::env!("A");
+                const _: ::core::option::Option<&str> = ::core::option_env!("B");
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_cfg_variants() {
+        let args = parse(quote::quote! {
+            cfg(target_os = "android") = "com.example.app.Native",
+            cfg(debug_assertions) = "com.example.debug.Native",
+            default = "com.example.Native",
+        })
+        .unwrap();
+
+        let variants = args.cfg_variants().unwrap();
+        let variants: Vec<_> = variants
+            .iter()
+            .map(|(predicate, args)| {
+                (
+                    predicate.as_ref().unwrap().to_string(),
+                    args.namespace().unwrap().value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            variants,
+            [
+                (
+                    quote::quote! { target_os = "android" }.to_string(),
+                    "com.example.app.Native".to_string()
+                ),
+                (
+                    quote::quote! { all(debug_assertions, not(any(target_os = "android"))) }
+                        .to_string(),
+                    "com.example.debug.Native".to_string()
+                ),
+                (
+                    quote::quote! { not(any(target_os = "android", debug_assertions)) }.to_string(),
+                    "com.example.Native".to_string()
+                ),
+            ]
+        );
+
+        let args = parse(quote::quote! { cfg(unix) = "a.B", class = "C" }).unwrap();
+        assert!(args.cfg_variants().is_err());
+
+        let args = parse(quote::quote! { cfg(any()) = "a.B" }).unwrap();
+        assert_eq!(
+            args.cfg_variants().err().unwrap().to_string(),
+            "`cfg` namespaces need a `default = \"...\"` namespace for when none of their predicates hold"
+        );
+    }
+
+    #[test]
+    fn test_kotlin_names() {
+        let ident: syn::Ident = syn::parse_quote!(compute);
+
+        let args = parse(quote::quote! { kotlin_file = "com.example.Utils" }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.UtilsKt");
+
+        let args = parse(quote::quote! { kotlin_companion = "com.example.Foo" }).unwrap();
+        assert_eq!(args.namespace().unwrap().value, "com.example.Foo$Companion");
+
+        let args = parse(quote::quote! {
+            "com.example.Foo", kotlin_internal_module = "app_release"
+        })
+        .unwrap();
+        assert_eq!(args.method_name(&ident).unwrap(), "compute$app_release");
+
+        let args = parse(quote::quote! {
+            kotlin_file = "com.example.Utils", kotlin_companion = "com.example.Foo"
+        })
+        .unwrap();
+        assert!(args.namespace().is_err());
+
+        let args =
+            parse(quote::quote! { "com.example.Foo", kotlin_file = "com.example.Utils" }).unwrap();
+        assert!(args.namespace().is_err());
+    }
+
+    #[test]
+    fn test_with_overrides() {
+        let ident: syn::Ident = syn::parse_quote!(say_hello);
+        let container = parse(
+            quote::quote! { package = "com.example", class = "Foo", rename_all = "camelCase" },
+        )
+        .unwrap();
+
+        let args = container.with_overrides(parse(quote::quote! {}).unwrap());
+        assert_eq!(args.namespace().unwrap().value, "com.example.Foo");
+        assert_eq!(args.method_name(&ident).unwrap(), "sayHello");
+
+        let args = container.with_overrides(parse(quote::quote! { class = "Bar" }).unwrap());
+        assert_eq!(args.namespace().unwrap().value, "com.example.Bar");
+
+        let args = container.with_overrides(
+            parse(quote::quote! { "org.example.Baz", rename_all = "verbatim" }).unwrap(),
+        );
+        assert_eq!(args.namespace().unwrap().value, "org.example.Baz");
+        assert_eq!(args.method_name(&ident).unwrap(), "say_hello");
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        assert!(parse(quote::quote! { "com.example.Foo", class = "Bar" })
+            .unwrap()
+            .namespace()
+            .is_err());
+        assert!(parse(quote::quote! { package = "com.example" })
+            .unwrap()
+            .namespace()
+            .is_err());
+        assert!(parse(quote::quote! { class = "Foo", class = "Bar" }).is_err());
+        assert!(parse(quote::quote! { class = "Foo" "Bar" }).is_err());
+        assert!(parse(quote::quote! { class = Foo })
+            .unwrap()
+            .namespace()
+            .is_err());
+        assert!(parse(quote::quote! { class = "Foo", "com.example.Bar" }).is_err());
+        assert!(parse(quote::quote! { "com.example.Foo", relaxed, relaxed }).is_err());
+        assert!(parse(quote::quote! { "com.example.Foo", register, register }).is_err());
+    }
+}
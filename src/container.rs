@@ -0,0 +1,260 @@
+//! Expansion of the `jni` attribute on `mod` and inherent `impl` blocks.
+//!
+//! Every public function in the container is exported under the container's namespace, as if it
+//! had its own `jni` attribute: the function is kept, and exported shims are added next to it.
+//! Functions may carry a `#[jni(...)]` attribute of their own to override the container's options,
+//! or to export a non-`pub` function (which is then rejected with the usual visibility error).
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+
+use crate::args::JniArgs;
+use crate::{
+    allow_non_snake_case, create_signature_const, expand_jni_fn_variants, forwarder_symbol, types,
+    Errors, ExportContext,
+};
+
+/// Exports the public functions of an inline `mod` block. Without `args`, which are then invalid,
+/// `jni` attributes are still removed from the functions but nothing is exported.
+pub(crate) fn expand_jni_mod(
+    args: Option<&JniArgs>,
+    mut module: syn::ItemMod,
+    errors: &mut Errors,
+) -> TokenStream {
+    let args = args.filter(|args| errors.check(check_container_args(args)).is_some());
+
+    let mut exports = Exports::default();
+    let mut env_dependencies = TokenStream::new();
+    let (_, items) = module
+        .content
+        .as_mut()
+        .expect("only inline modules are expanded");
+    for item in std::mem::take(items) {
+        let syn::Item::Fn(mut function) = item else {
+            items.push(item);
+            continue;
+        };
+        let function_args =
+            errors.check(take_function_args(args, &mut function.attrs, &function.vis));
+        let Some(function_args) = function_args.flatten() else {
+            items.push(syn::Item::Fn(function));
+            continue;
+        };
+        env_dependencies.extend(function_args.env_dependencies());
+
+        let shims = errors
+            .check(expand_jni_fn_variants(
+                &function_args,
+                &function,
+                ExportContext::Item,
+            ))
+            .unwrap_or_default();
+        // colliding shims are left out, so that only the collision is reported
+        let shims: Vec<_> = shims
+            .into_iter()
+            .filter(|(predicate, shim)| {
+                let symbol = forwarder_symbol(&shim.function);
+                let inserted = exports.insert(&function.sig.ident, predicate.as_ref(), symbol);
+                errors.check(inserted).is_some()
+            })
+            .collect();
+        let signature = match shims.is_empty() {
+            true => None,
+            false => create_signature_const(&function_args, &function),
+        };
+        types::strip_jtype_attrs(&mut function.sig);
+        allow_non_snake_case(&function.sig.ident, &mut function.attrs);
+        items.push(syn::Item::Fn(function));
+        items.extend(signature.map(syn::Item::Verbatim));
+        for (_, shim) in shims {
+            let name = &shim.function.sig.ident;
+            let registration = shim.registration(&quote::quote! { #name });
+            items.push(syn::Item::Fn(shim.function));
+            items.extend(registration.map(syn::Item::Verbatim));
+        }
+    }
+
+    quote::quote! { #env_dependencies #module }
+}
+
+/// Exports the public associated functions of an inherent `impl` block, like `expand_jni_mod`.
+/// These can't take `self`, since the JVM has no way to pass one.
+pub(crate) fn expand_jni_impl(
+    args: Option<&JniArgs>,
+    mut block: syn::ItemImpl,
+    errors: &mut Errors,
+) -> TokenStream {
+    let args = args.filter(|args| errors.check(check_container_args(args)).is_some());
+
+    let mut exports = Exports::default();
+    let mut env_dependencies = TokenStream::new();
+    // registered functions are referred to from outside the block, which can't contain them
+    let mut registrations = TokenStream::new();
+    let self_ty = block.self_ty.clone();
+    for item in std::mem::take(&mut block.items) {
+        let syn::ImplItem::Fn(mut method) = item else {
+            block.items.push(item);
+            continue;
+        };
+        let function_args = errors.check(take_function_args(args, &mut method.attrs, &method.vis));
+        let Some(function_args) = function_args.flatten() else {
+            block.items.push(syn::ImplItem::Fn(method));
+            continue;
+        };
+        env_dependencies.extend(function_args.env_dependencies());
+
+        if let Some(receiver) = method.sig.receiver() {
+            errors.push(syn::Error::new(
+                receiver.span(),
+                "`jni_fn` exported functions in `impl` blocks can't take `self`",
+            ));
+            block.items.push(syn::ImplItem::Fn(method));
+            continue;
+        }
+
+        let function = syn::ItemFn {
+            attrs: method.attrs.clone(),
+            vis: method.vis.clone(),
+            sig: method.sig.clone(),
+            block: Box::new(method.block.clone()),
+        };
+        let shims = errors
+            .check(expand_jni_fn_variants(
+                &function_args,
+                &function,
+                ExportContext::ImplBlock,
+            ))
+            .unwrap_or_default();
+        // colliding shims are left out, so that only the collision is reported
+        let shims: Vec<_> = shims
+            .into_iter()
+            .filter(|(predicate, shim)| {
+                let symbol = forwarder_symbol(&shim.function);
+                let inserted = exports.insert(&method.sig.ident, predicate.as_ref(), symbol);
+                errors.check(inserted).is_some()
+            })
+            .collect();
+        let signature = match shims.is_empty() {
+            true => None,
+            false => create_signature_const(&function_args, &function),
+        };
+        types::strip_jtype_attrs(&mut method.sig);
+        allow_non_snake_case(&method.sig.ident, &mut method.attrs);
+        block.items.push(syn::ImplItem::Fn(method));
+        block.items.extend(signature.map(syn::ImplItem::Verbatim));
+        block.items.extend(shims.into_iter().map(|(_, shim)| {
+            let name = &shim.function.sig.ident;
+            registrations.extend(shim.registration(&quote::quote! { <#self_ty>::#name }));
+            syn::ImplItem::Fn(syn::ImplItemFn {
+                attrs: shim.function.attrs,
+                vis: shim.function.vis,
+                defaultness: None,
+                sig: shim.function.sig,
+                block: *shim.function.block,
+            })
+        }));
+    }
+
+    quote::quote! { #env_dependencies #block #registrations }
+}
+
+/// Rejects options which only make sense for a single function.
+fn check_container_args(args: &JniArgs) -> syn::Result<()> {
+    for (name, value) in [("method", &args.method), ("signature", &args.signature)] {
+        if let Some(value) = value {
+            return Err(syn::Error::new(
+                value.span(),
+                format!(
+                    "The `{}` option can't be applied to a `mod` or `impl` block; move it to one of its functions",
+                    name
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Decides whether a function inside a container should be exported, returning its arguments if
+/// so. Any `jni` attribute on the function is removed, and its options take precedence over the
+/// container's; without one, only `pub` functions are exported. Without `container_args`, the
+/// attribute is only removed.
+fn take_function_args(
+    container_args: Option<&JniArgs>,
+    attrs: &mut Vec<syn::Attribute>,
+    vis: &syn::Visibility,
+) -> syn::Result<Option<JniArgs>> {
+    let mut overrides = None;
+    let mut index = 0;
+    while index < attrs.len() {
+        let is_jni = attrs[index]
+            .path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "jni");
+        if !is_jni {
+            index += 1;
+            continue;
+        }
+
+        let attr = attrs.remove(index);
+        if overrides.is_some() {
+            return Err(syn::Error::new(
+                attr.span(),
+                "Only one `jni_fn` attribute may be applied to a function",
+            ));
+        }
+        let parsed = match &attr.meta {
+            syn::Meta::Path(_) => JniArgs::default(),
+            syn::Meta::List(list) => syn::parse2::<JniArgs>(list.tokens.clone())?,
+            syn::Meta::NameValue(_) => {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    "Expected `#[jni]` or `#[jni(...)]` on a function in a `jni_fn` container",
+                ))
+            }
+        };
+        overrides = Some(parsed);
+    }
+
+    let Some(container_args) = container_args else {
+        return Ok(None);
+    };
+    match overrides {
+        Some(overrides) => Ok(Some(container_args.with_overrides(overrides))),
+        None if matches!(vis, syn::Visibility::Public(_)) => Ok(Some(container_args.clone())),
+        None => Ok(None),
+    }
+}
+
+/// Tracks the JNI symbols exported from a container, so that functions which collapse to the same
+/// Java method (e.g. through `rename_all`) are reported instead of failing to link. Exports gated
+/// behind different `cfg` predicates are tracked separately.
+#[derive(Default)]
+struct Exports(HashMap<(String, String), syn::Ident>);
+
+impl Exports {
+    fn insert(
+        &mut self,
+        original: &syn::Ident,
+        predicate: Option<&TokenStream>,
+        symbol: String,
+    ) -> syn::Result<()> {
+        let key = (
+            predicate.map(ToString::to_string).unwrap_or_default(),
+            symbol.clone(),
+        );
+        if let Some(previous) = self.0.get(&key) {
+            return Err(syn::Error::new(
+                original.span(),
+                format!(
+                    "`{}` is exported as `{}`, which is already used by `{}`",
+                    original, symbol, previous
+                ),
+            ));
+        }
+        self.0.insert(key, original.clone());
+        Ok(())
+    }
+}
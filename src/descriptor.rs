@@ -0,0 +1,327 @@
+//! JVM type descriptors, as described in section 4.3 of the Java Virtual Machine specification.
+//!
+//! Method descriptors are needed to export overloaded native methods, whose JNI symbol names carry
+//! a mangled copy of the argument types, and to register native methods. Class names may also be
+//! copied from `javap` output or from headers generated by `javac -h`, which use the JVM's internal
+//! form.
+
+/// A method descriptor which has been checked by `parse_method_descriptor`, like
+/// `(ILjava/lang/String;)V`.
+pub(crate) struct MethodDescriptor<'a> {
+    /// The argument types, without the surrounding parentheses; e.g. `ILjava/lang/String;`.
+    pub(crate) args: &'a str,
+    /// The return type, which is `V` for `void`.
+    pub(crate) ret: &'a str,
+}
+
+impl<'a> MethodDescriptor<'a> {
+    /// Splits the argument types into the descriptors of the individual arguments.
+    pub(crate) fn arg_types(&self) -> Vec<&'a str> {
+        let mut types = Vec::new();
+        let mut remaining = self.args;
+        while !remaining.is_empty() {
+            let rest = parse_field_type(remaining).expect("the descriptor was checked when parsed");
+            types.push(&remaining[..remaining.len() - rest.len()]);
+            remaining = rest;
+        }
+        types
+    }
+}
+
+/// The JVM type of a parameter or return value, as far as its Rust type tells.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum FieldType {
+    /// A type whose descriptor is known, like `I` for `jint` or `Ljava/lang/String;` for `JString`.
+    Exact(String),
+    /// A reference type which may be any type whose descriptor starts with one of `kinds`, like
+    /// `JObject`; unless it is refined, it is declared as `descriptor`.
+    Erased {
+        descriptor: &'static str,
+        kinds: &'static [&'static str],
+    },
+}
+
+impl FieldType {
+    pub(crate) fn descriptor(&self) -> &str {
+        match self {
+            FieldType::Exact(descriptor) => descriptor,
+            FieldType::Erased { descriptor, .. } => descriptor,
+        }
+    }
+
+    /// Returns whether a value of this type can be declared as the field descriptor `descriptor`
+    /// on the Java side.
+    pub(crate) fn accepts(&self, descriptor: &str) -> bool {
+        match self {
+            FieldType::Exact(exact) => exact == descriptor,
+            FieldType::Erased { kinds, .. } => {
+                kinds.iter().any(|kind| descriptor.starts_with(kind))
+            }
+        }
+    }
+}
+
+/// Checks that `descriptor` is a well-formed method descriptor, returning a human-readable reason
+/// if it isn't.
+pub(crate) fn parse_method_descriptor(descriptor: &str) -> Result<MethodDescriptor<'_>, String> {
+    let Some(rest) = descriptor.strip_prefix('(') else {
+        return Err("method descriptors must start with `(`".to_string());
+    };
+    let Some(args_end) = rest.find(')') else {
+        return Err("missing `)` after the argument types".to_string());
+    };
+    let args = &rest[..args_end];
+    let ret = &rest[args_end + 1..];
+
+    let mut remaining = args;
+    while !remaining.is_empty() {
+        remaining = parse_field_type(remaining)?;
+    }
+
+    if ret == "V" {
+        return Ok(MethodDescriptor { args, ret });
+    }
+    match parse_field_type(ret) {
+        Ok("") => Ok(MethodDescriptor { args, ret }),
+        Ok(trailing) => Err(format!("unexpected `{}` after the return type", trailing)),
+        Err(_) if ret.is_empty() => Err("missing return type after `)`".to_string()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Converts a class name in the JVM's internal form (`com/example/Foo$Bar`, section 4.2.1), or
+/// given as a field descriptor (`Lcom/example/Foo;`), into the `.`-separated binary name that the
+/// `jni` attribute uses (`com.example.Foo$Bar`). Binary names are returned unchanged; a name which
+/// mixes both forms is rejected with a human-readable reason.
+pub(crate) fn binary_class_name(name: &str) -> Result<String, String> {
+    let internal = match name.strip_prefix('L').and_then(|n| n.strip_suffix(';')) {
+        Some(internal) => {
+            if internal.contains('.') {
+                return Err(format!(
+                    "the descriptor `{}` must use `/` as a package separator; write `L{};` or `{}`",
+                    name,
+                    internal.replace('.', "/"),
+                    internal
+                ));
+            }
+            internal
+        }
+        None if name.starts_with('[') => {
+            return Err(format!(
+                "`{}` is an array type, which can't declare native methods",
+                name
+            ))
+        }
+        None => name,
+    };
+
+    if internal.contains('/') && internal.contains('.') {
+        return Err(format!(
+            "`{}` mixes the `/`-separated internal form with the `.`-separated binary form; write `{}` or `{}`",
+            name,
+            internal.replace('.', "/"),
+            internal.replace('/', ".")
+        ));
+    }
+    Ok(internal.replace('/', "."))
+}
+
+/// Converts a Java type, as given to `#[jtype("...")]`, into a field descriptor. The type may be a
+/// primitive like `int`, a class name in any of the forms accepted by `binary_class_name`, or a
+/// field descriptor like `[I`, followed by any number of `[]`.
+pub(crate) fn java_type_descriptor(java_type: &str) -> Result<String, String> {
+    let mut element = java_type;
+    let mut dimensions = 0;
+    while let Some(inner) = element.strip_suffix("[]") {
+        element = inner;
+        dimensions += 1;
+    }
+    if let Some(start) = element.find('<') {
+        return Err(format!(
+            "type arguments aren't part of descriptors; write `{}`",
+            &element[..start]
+        ));
+    }
+    let element = match element {
+        "boolean" => "Z".to_string(),
+        "byte" => "B".to_string(),
+        "char" => "C".to_string(),
+        "short" => "S".to_string(),
+        "int" => "I".to_string(),
+        "long" => "J".to_string(),
+        "float" => "F".to_string(),
+        "double" => "D".to_string(),
+        "void" => return Err("`void` is only allowed as a return type".to_string()),
+        "" => return Err("missing type".to_string()),
+        array if array.starts_with('[') => array.to_string(),
+        class => format!("L{};", binary_class_name(class)?.replace('.', "/")),
+    };
+
+    let descriptor = format!("{}{}", "[".repeat(dimensions), element);
+    match parse_field_type(&descriptor) {
+        Ok("") => Ok(descriptor),
+        Ok(trailing) => Err(format!("unexpected `{}` after the type", trailing)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a single field type from the start of `input`, returning whatever follows it.
+fn parse_field_type(input: &str) -> Result<&str, String> {
+    let mut chars = input.chars();
+    match chars.next() {
+        Some('B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z') => Ok(chars.as_str()),
+        Some('[') => {
+            let rest = chars.as_str();
+            if rest.is_empty() {
+                return Err("missing element type after `[`".to_string());
+            }
+            parse_field_type(rest)
+        }
+        Some('L') => {
+            let rest = chars.as_str();
+            let Some(end) = rest.find(';') else {
+                return Err(format!("missing `;` after class name in `L{}`", rest));
+            };
+            let class = &rest[..end];
+            if class.split('/').any(|segment| segment.is_empty()) {
+                return Err(format!("empty class name segment in `L{};`", class));
+            }
+            if let Some(c) = class.chars().find(|c| matches!(c, '.' | '[' | '(' | ')')) {
+                return Err(format!(
+                    "unexpected `{}` in class name `{}` (use `/` as a package separator)",
+                    c, class
+                ));
+            }
+            Ok(&rest[end + 1..])
+        }
+        Some('V') => Err("`V` (void) is only allowed as a return type".to_string()),
+        Some(c) => Err(format!("unknown type `{}`", c)),
+        None => Err("missing type".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method_descriptor() {
+        assert_eq!(parse_method_descriptor("()V").unwrap().args, "");
+        assert_eq!(
+            parse_method_descriptor("(ILjava/lang/String;)V")
+                .unwrap()
+                .args,
+            "ILjava/lang/String;"
+        );
+        assert_eq!(
+            parse_method_descriptor("([B[[Ljava/lang/Object;JZ)[I")
+                .unwrap()
+                .args,
+            "[B[[Ljava/lang/Object;JZ"
+        );
+        assert_eq!(
+            parse_method_descriptor("(Ljava/lang/String;)Ljava/lang/String;")
+                .unwrap()
+                .args,
+            "Ljava/lang/String;"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_method_descriptor() {
+        assert!(parse_method_descriptor("").is_err());
+        assert!(parse_method_descriptor("I)V").is_err());
+        assert!(parse_method_descriptor("(I").is_err());
+        assert!(parse_method_descriptor("(I)").is_err());
+        assert!(parse_method_descriptor("(V)V").is_err());
+        assert!(parse_method_descriptor("(Q)V").is_err());
+        assert!(parse_method_descriptor("([)V").is_err());
+        assert!(parse_method_descriptor("(Ljava/lang/String)V").is_err());
+        assert!(parse_method_descriptor("(Ljava.lang.String;)V").is_err());
+        assert!(parse_method_descriptor("(L;)V").is_err());
+        assert!(parse_method_descriptor("()VV").is_err());
+        assert!(parse_method_descriptor("()II").is_err());
+    }
+
+    #[test]
+    fn test_arg_types() {
+        let descriptor = parse_method_descriptor("(I[[JLjava/lang/String;Z)[B").unwrap();
+        assert_eq!(
+            descriptor.arg_types(),
+            ["I", "[[J", "Ljava/lang/String;", "Z"]
+        );
+        assert_eq!(descriptor.ret, "[B");
+        assert!(parse_method_descriptor("()V")
+            .unwrap()
+            .arg_types()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_java_type_descriptor() {
+        for (java_type, descriptor) in [
+            ("int", "I"),
+            ("boolean[]", "[Z"),
+            ("java.util.List", "Ljava/util/List;"),
+            ("java/util/Map$Entry", "Ljava/util/Map$Entry;"),
+            ("Ljava/lang/String;", "Ljava/lang/String;"),
+            ("java.lang.String[][]", "[[Ljava/lang/String;"),
+            ("[J", "[J"),
+        ] {
+            assert_eq!(java_type_descriptor(java_type).unwrap(), descriptor);
+        }
+
+        assert_eq!(
+            java_type_descriptor("java.util.List<String>").unwrap_err(),
+            "type arguments aren't part of descriptors; write `java.util.List`"
+        );
+        assert!(java_type_descriptor("void").is_err());
+        assert!(java_type_descriptor("[]").is_err());
+        assert!(java_type_descriptor("[Q").is_err());
+        assert!(java_type_descriptor("[II").is_err());
+        assert!(java_type_descriptor("com/example.Foo").is_err());
+    }
+
+    #[test]
+    fn test_field_type_accepts() {
+        let object = FieldType::Erased {
+            descriptor: "Ljava/lang/Object;",
+            kinds: &["L", "["],
+        };
+        assert!(object.accepts("Ljava/util/List;"));
+        assert!(object.accepts("[I"));
+        assert!(!object.accepts("I"));
+
+        let string = FieldType::Exact("Ljava/lang/String;".to_string());
+        assert!(string.accepts("Ljava/lang/String;"));
+        assert!(!string.accepts("Ljava/lang/Object;"));
+    }
+
+    #[test]
+    fn test_binary_class_name() {
+        assert_eq!(
+            binary_class_name("com.example.Foo").unwrap(),
+            "com.example.Foo"
+        );
+        assert_eq!(
+            binary_class_name("com/example/Foo$Bar").unwrap(),
+            "com.example.Foo$Bar"
+        );
+        assert_eq!(
+            binary_class_name("Lcom/example/Foo;").unwrap(),
+            "com.example.Foo"
+        );
+        assert_eq!(binary_class_name("LFoo;").unwrap(), "Foo");
+        assert_eq!(binary_class_name("Foo").unwrap(), "Foo");
+
+        assert_eq!(
+            binary_class_name("com/example.Foo").unwrap_err(),
+            "`com/example.Foo` mixes the `/`-separated internal form with the `.`-separated binary form; write `com/example/Foo` or `com.example.Foo`"
+        );
+        assert_eq!(
+            binary_class_name("Lcom.example.Foo;").unwrap_err(),
+            "the descriptor `Lcom.example.Foo;` must use `/` as a package separator; write `Lcom/example/Foo;` or `com.example.Foo`"
+        );
+        assert!(binary_class_name("[Lcom/example/Foo;").is_err());
+    }
+}
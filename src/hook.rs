@@ -0,0 +1,917 @@
+//! Entry points of JNI hooks like `JNI_OnLoad`, which the JVM calls as
+//! `jint JNI_OnLoad(JavaVM *vm, void *reserved)`.
+//!
+//! Hook functions may take the VM as a `jni::JavaVM`, by value or by reference, in which case the
+//! entry point converts the raw pointer before calling them; or they may take the raw pointers
+//! themselves, which are passed through as they are.
+//!
+//! `on_load` hooks may also declare the JNI versions they support, in which case the entry point
+//! asks the VM for the newest of them with `GetEnv` and returns it on their behalf.
+
+use proc_macro2::{Span, TokenStream};
+use syn::spanned::Spanned;
+use syn::ItemFn;
+
+use crate::registry::{self, Registry};
+use crate::{export_attrs, JniExportType, SymbolStyle};
+
+/// The arguments of a hook attribute: an optional library name, as in `#[on_load(example)]` or
+/// `#[on_load("example")]`, followed by options like `version = "1.8"`.
+#[derive(Default)]
+pub(crate) struct HookArgs {
+    /// The library name of a static hook, or an empty string for a dynamic hook.
+    pub(crate) libname: String,
+    /// Set by `auto` in place of a library name, which exports the hook both dynamically and for
+    /// a static library named after the crate.
+    pub(crate) auto: bool,
+    /// The predicate of `auto(...)`, under which the hook is only exported for a static library,
+    /// and otherwise only dynamically.
+    pub(crate) static_cfg: Option<syn::Meta>,
+    /// Set by `unchecked_libname`, which allows a library name other than the crate's.
+    pub(crate) unchecked_libname: bool,
+    pub(crate) version: Option<syn::LitStr>,
+    pub(crate) min: Option<syn::LitStr>,
+}
+
+/// The JNI versions which can be negotiated, named as in `version = "..."`, with the values of
+/// their `JNI_VERSION_*` constants. These are in ascending order.
+const JNI_VERSIONS: &[(&str, i32)] = &[
+    ("1.1", 0x0001_0001),
+    ("1.2", 0x0001_0002),
+    ("1.4", 0x0001_0004),
+    ("1.6", 0x0001_0006),
+    ("1.8", 0x0001_0008),
+    ("9", 0x0009_0000),
+    ("10", 0x000a_0000),
+    ("19", 0x0013_0000),
+    ("20", 0x0014_0000),
+    ("21", 0x0015_0000),
+    ("24", 0x0018_0000),
+];
+
+/// The oldest version which a `JNI_OnLoad_<lib>` hook of a static library may return.
+const MIN_STATIC_VERSION: i32 = 0x0001_0008;
+
+/// The JNI versions an `on_load` hook supports, of which the entry point returns the newest that
+/// the VM supports as well.
+pub(crate) struct Negotiation {
+    /// The values of the supported versions, newest first.
+    versions: Vec<i32>,
+}
+
+impl Negotiation {
+    /// Resolves the `version` and `min` options of a hook. Without a `min`, only `version` itself
+    /// is supported. `static_symbol` is the name of the hook when it is exported for a static
+    /// library, which must support at least JNI 1.8.
+    pub(crate) fn from_args(
+        args: &HookArgs,
+        static_symbol: Option<&str>,
+    ) -> syn::Result<Option<Negotiation>> {
+        let Some(version) = &args.version else {
+            return match &args.min {
+                Some(min) => Err(syn::Error::new(
+                    min.span(),
+                    "`min` needs a `version` to negotiate up to",
+                )),
+                None => Ok(None),
+            };
+        };
+        let max = parse_version(version)?;
+        let (min, min_literal) = match &args.min {
+            Some(min) => (parse_version(min)?, min),
+            None => (max, version),
+        };
+        if min > max {
+            return Err(syn::Error::new(
+                min_literal.span(),
+                format!(
+                    "`min` can't be newer than `version` (\"{}\")",
+                    version.value()
+                ),
+            ));
+        }
+        if let Some(symbol) = static_symbol {
+            if min < MIN_STATIC_VERSION {
+                return Err(syn::Error::new(
+                    min_literal.span(),
+                    format!(
+                        "`{}` is called for a statically linked library, which must support at least JNI 1.8; raise the {} to \"1.8\"",
+                        symbol,
+                        if args.min.is_some() { "`min`" } else { "`version`" }
+                    ),
+                ));
+            }
+        }
+
+        let versions = JNI_VERSIONS
+            .iter()
+            .rev()
+            .map(|&(_, value)| value)
+            .filter(|value| (min..=max).contains(value))
+            .collect();
+        Ok(Some(Negotiation { versions }))
+    }
+}
+
+fn parse_version(literal: &syn::LitStr) -> syn::Result<i32> {
+    let name = literal.value();
+    match JNI_VERSIONS.iter().find(|(version, _)| *version == name) {
+        Some(&(_, value)) => Ok(value),
+        None => {
+            let names: Vec<&str> = JNI_VERSIONS.iter().map(|(version, _)| *version).collect();
+            Err(syn::Error::new(
+                literal.span(),
+                format!(
+                    "Unknown JNI version `{}`; expected one of {}",
+                    name,
+                    names.join(", ")
+                ),
+            ))
+        }
+    }
+}
+
+/// Rejects a static `on_load` hook which evidently returns a version older than JNI 1.8, by
+/// returning one of the older `JNI_VERSION_*` constants. Versions computed at runtime can't be
+/// checked.
+pub(crate) fn check_static_version(function: &ItemFn, symbol: &str) -> syn::Result<()> {
+    let returned = function.block.stmts.iter().filter_map(|stmt| match stmt {
+        syn::Stmt::Expr(syn::Expr::Return(ret), _) => ret.expr.as_deref(),
+        syn::Stmt::Expr(expr, None) => Some(expr),
+        _ => None,
+    });
+    for expr in returned {
+        let syn::Expr::Path(path) = expr else {
+            continue;
+        };
+        let Some(constant) = path.path.segments.last() else {
+            continue;
+        };
+        let old = ["1_1", "1_2", "1_4", "1_6"]
+            .iter()
+            .any(|version| constant.ident == format!("JNI_VERSION_{}", version));
+        if old {
+            return Err(syn::Error::new(
+                expr.span(),
+                format!(
+                    "`{}` is called for a statically linked library, which must return at least `JNI_VERSION_1_8`",
+                    symbol
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// How a hook function takes the VM it is loaded into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum VmParam {
+    /// `vm: jni::JavaVM`
+    Owned,
+    /// `vm: &jni::JavaVM`
+    Borrowed,
+    /// `vm: *mut jni::sys::JavaVM, reserved: *mut c_void`, as the JVM passes them, or the
+    /// parameters of an agent's entry point.
+    Raw,
+}
+
+/// What a hook function returns. Errors are logged, and fail loading for `on_load` hooks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum HookOutput {
+    /// Nothing, as `on_unload` hooks and `on_load` hooks with a negotiated version do.
+    Unit,
+    /// The JNI version an `on_load` hook needs, or the status an agent returns, as a `jint`.
+    Version,
+    /// `Result<(), E>`
+    UnitResult,
+    /// `Result<jint, E>`
+    VersionResult,
+}
+
+/// How a hook function is called, as found by `check_hook_signature`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct HookSignature {
+    pub(crate) vm: VmParam,
+    /// Whether an agent takes its options as an `options: &str` after the VM.
+    pub(crate) options: bool,
+    pub(crate) output: HookOutput,
+}
+
+/// The parameters of the entry point of a hook, with the names they are given and the types they
+/// point to. Hooks taking the raw pointers take exactly these.
+fn raw_params(export: JniExportType) -> &'static [(&'static str, &'static str)] {
+    match export {
+        JniExportType::OnLoad | JniExportType::OnUnload => {
+            &[("__jni_vm", "JavaVM"), ("__jni_reserved", "c_void")]
+        }
+        JniExportType::AgentOnLoad | JniExportType::AgentOnAttach => &[
+            ("__jni_vm", "JavaVM"),
+            ("__jni_options", "c_char"),
+            ("__jni_reserved", "c_void"),
+        ],
+        JniExportType::AgentOnUnload => &[("__jni_vm", "JavaVM")],
+    }
+}
+
+/// Whether the entry point of a hook returns a `jint`, which is `JNI_ERR` if the hook fails.
+fn returns_status(export: JniExportType) -> bool {
+    !matches!(
+        export,
+        JniExportType::OnUnload | JniExportType::AgentOnUnload
+    )
+}
+
+/// Checks the signature of a hook function, returning how it takes the VM and what it returns.
+/// `on_load` hooks must return the JNI version they need, unless it is negotiated for them, and
+/// `on_unload` hooks can't return anything; either may wrap that in a `Result`. Agents loaded or
+/// attached may also take their options, and return a status or nothing.
+pub(crate) fn check_hook_signature(
+    sig: &syn::Signature,
+    export: JniExportType,
+    hook_name: &str,
+    negotiates: bool,
+) -> syn::Result<HookSignature> {
+    let params: Vec<&syn::PatType> = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(arg) => Some(arg),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect();
+
+    let raw = raw_params(export);
+    let is_raw = params.len() == raw.len()
+        && params
+            .iter()
+            .zip(raw)
+            .all(|(param, (_, pointee))| is_pointer_to(&param.ty, pointee));
+    let takes_options = raw.iter().any(|(name, _)| *name == "__jni_options");
+    let vm = match params.as_slice() {
+        [vm, ..] if is_sys_vm(&vm.ty) => {
+            return Err(syn::Error::new(
+                vm.ty.span(),
+                "`jni::sys::JavaVM` is a pointer to the VM's function table, not the VM; take `vm: jni::JavaVM`, or the raw `vm: *mut jni::sys::JavaVM, reserved: *mut c_void` pointers",
+            ))
+        }
+        _ if is_raw => Some((VmParam::Raw, false)),
+        [vm] => vm_param(&vm.ty).map(|vm| (vm, false)),
+        [vm, options] if takes_options && is_str(&options.ty) => {
+            vm_param(&vm.ty).map(|vm| (vm, true))
+        }
+        _ => None,
+    };
+    let Some((vm, options)) = vm else {
+        let raw = raw
+            .iter()
+            .map(|(name, pointee)| {
+                let pointee = match *pointee {
+                    "JavaVM" => "jni::sys::JavaVM",
+                    pointee => pointee,
+                };
+                format!("{}: *mut {}", name.trim_start_matches("__jni_"), pointee)
+            })
+            .collect::<Vec<_>>();
+        return Err(syn::Error::new(
+            params
+                .first()
+                .map_or(sig.paren_token.span.join(), |param| param.span()),
+            format!(
+                "`{}` hooks must take the VM as `vm: jni::JavaVM` or `vm: &jni::JavaVM`{}, or the raw `{}` {}",
+                hook_name,
+                if takes_options { ", optionally followed by `options: &str`" } else { "" },
+                raw.join(", "),
+                if raw.len() == 1 { "pointer" } else { "pointers" }
+            ),
+        ));
+    };
+
+    let output = match &sig.output {
+        syn::ReturnType::Type(_, ty) => match result_ok_type(ty) {
+            Some(ok) if is_unit(ok) => HookOutput::UnitResult,
+            Some(_) => HookOutput::VersionResult,
+            None if is_unit(ty) => HookOutput::Unit,
+            None => HookOutput::Version,
+        },
+        syn::ReturnType::Default => HookOutput::Unit,
+    };
+    let span = match &sig.output {
+        syn::ReturnType::Type(_, ty) => ty.span(),
+        syn::ReturnType::Default => sig.paren_token.span.join(),
+    };
+    let message = match (export, output) {
+        (JniExportType::OnLoad, HookOutput::Version | HookOutput::VersionResult) if negotiates => {
+            "`on_load` hooks with a `version` can only return `()` or `Result<(), E>`, since the negotiated version is returned for them"
+        }
+        (JniExportType::OnLoad, HookOutput::Unit) if !negotiates => {
+            "`on_load` hooks must return the JNI version they need as a `jint`, like `JNI_VERSION_1_8`"
+        }
+        (JniExportType::OnLoad, HookOutput::UnitResult) if !negotiates => {
+            "`on_load` hooks returning `Result<(), E>` need a `version` to negotiate; add `version = \"...\"`, or return the JNI version they need as `Ok`"
+        }
+        (
+            JniExportType::OnUnload | JniExportType::AgentOnUnload,
+            HookOutput::Version | HookOutput::VersionResult,
+        ) => {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "`{}` hooks can only return `()` or `Result<(), E>`",
+                    hook_name
+                ),
+            ))
+        }
+        _ => return Ok(HookSignature { vm, options, output }),
+    };
+    Err(syn::Error::new(span, message))
+}
+
+/// Creates the exported entry point of a hook, which forwards the VM to the hook function and, if
+/// there is a `negotiation`, returns the newest JNI version which both the hook and the VM support.
+///
+/// Before an `on_load` hook, the native methods of `#[jni(register)]` functions are registered and
+/// the functions registered with `on_load_init` are run, and loading fails if any of these fails.
+/// The functions registered with `on_unload_cleanup` run after an `on_unload` hook.
+///
+/// The options of an agent are passed as a `&str`, which is empty if the JVM passes none, and make
+/// the agent fail to load if they aren't UTF-8.
+pub(crate) fn create_entry_point(
+    target: &ItemFn,
+    symbol: &str,
+    signature: HookSignature,
+    export: JniExportType,
+    negotiation: Option<&Negotiation>,
+) -> ItemFn {
+    let HookSignature {
+        vm,
+        options,
+        output,
+    } = signature;
+    let failure = match returns_status(export) {
+        true => quote::quote! { ::jni::sys::JNI_ERR },
+        false => quote::quote! {},
+    };
+
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    let mut convert = Vec::new();
+    match vm {
+        VmParam::Raw => {
+            // the raw types are kept, since they may come from another version of `jni-sys`
+            for ((name, _), input) in raw_params(export).iter().zip(&target.sig.inputs) {
+                if let syn::FnArg::Typed(arg) = input {
+                    let name = quote::format_ident!("{}", name);
+                    let ty = &arg.ty;
+                    params.push(quote::quote! { #name: #ty });
+                    args.push(quote::quote! { #name });
+                }
+            }
+        }
+        VmParam::Owned | VmParam::Borrowed => {
+            for &(name, pointee) in raw_params(export) {
+                params.push(match (name, pointee) {
+                    ("__jni_vm", _) => quote::quote! { __jni_vm: *mut ::jni::sys::JavaVM },
+                    ("__jni_options", _) if options => {
+                        quote::quote! { __jni_options: *mut ::core::ffi::c_char }
+                    }
+                    (_, pointee) => {
+                        let pointee = quote::format_ident!("{}", pointee);
+                        quote::quote! { _: *mut ::core::ffi::#pointee }
+                    }
+                });
+            }
+            args.push(vm_arg(vm, &target.sig));
+            convert.push(vm_conversion(&failure));
+            if options {
+                args.push(quote::quote! { __jni_options });
+                let message = syn::LitStr::new(
+                    &format!("{} options aren't valid UTF-8: {{}}", symbol),
+                    target.sig.ident.span(),
+                );
+                convert.push(quote::quote! {
+                    let __jni_options = match __jni_options.is_null() {
+                        true => "",
+                        false => match unsafe { ::core::ffi::CStr::from_ptr(__jni_options) }.to_str() {
+                            ::core::result::Result::Ok(options) => options,
+                            ::core::result::Result::Err(error) => {
+                                ::std::eprintln!(#message, error);
+                                return ::jni::sys::JNI_ERR;
+                            }
+                        },
+                    };
+                });
+            }
+        }
+    }
+    let vm_pointer = match vm {
+        VmParam::Raw => quote::quote! { __jni_vm as *mut ::jni::sys::JavaVM },
+        _ => quote::quote! { __jni_vm },
+    };
+
+    let probe = negotiation.map(|negotiation| {
+        let versions = &negotiation.versions;
+        quote::quote! {
+            let __jni_version = {
+                let vm: *mut ::jni::sys::JavaVM = #vm_pointer;
+                let get_env = match vm.is_null() {
+                    true => ::core::option::Option::None,
+                    false => unsafe { (**vm).GetEnv },
+                };
+                [#(#versions),*].into_iter().find(|&version| {
+                    let mut env = ::core::ptr::null_mut();
+                    get_env.is_some_and(|get_env| {
+                        unsafe { get_env(vm, &mut env, version) == ::jni::sys::JNI_OK }
+                    })
+                })
+            };
+            let ::core::option::Option::Some(__jni_version) = __jni_version else {
+                return ::jni::sys::JNI_ERR;
+            };
+        }
+    });
+    // the registered functions are given the raw VM pointer, which is converted for the hook
+    let (before, after) = match export {
+        JniExportType::OnLoad => {
+            let natives = registry::register_natives(&vm_pointer);
+            let init = registry::run(Registry::Init, &vm_pointer);
+            (Some(quote::quote! { #natives #init }), None)
+        }
+        JniExportType::OnUnload => {
+            let registry_vm = quote::quote! { __jni_registry_vm };
+            (
+                Some(quote::quote! {
+                    let #registry_vm: *mut ::jni::sys::JavaVM = #vm_pointer;
+                }),
+                Some(registry::run(Registry::Cleanup, &registry_vm)),
+            )
+        }
+        _ => (None, None),
+    };
+
+    let target_name = &target.sig.ident;
+    let call = match target.sig.unsafety {
+        Some(_) => quote::quote! { unsafe { #target_name(#(#args),*) } },
+        None => quote::quote! { #target_name(#(#args),*) },
+    };
+    // what the entry point returns for a hook which doesn't return a version
+    let success = match negotiation {
+        Some(_) => quote::quote! { __jni_version },
+        None if returns_status(export) => quote::quote! { ::jni::sys::JNI_OK },
+        None => quote::quote! { {} },
+    };
+    let call = guarded_call(
+        &call,
+        output,
+        &success,
+        &failure,
+        symbol,
+        target_name.span(),
+    );
+
+    let attrs = target
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"));
+    let export_attrs = export_attrs(symbol, SymbolStyle::NoMangle);
+    let vis = &target.vis;
+    let unsafety = &target.sig.unsafety;
+    let name = syn::Ident::new(symbol, target_name.span());
+    let entry_output = match returns_status(export) {
+        true => quote::quote! { -> ::jni::sys::jint },
+        false => quote::quote! {},
+    };
+    syn::parse_quote! {
+        #(#attrs)*
+        #(#export_attrs)*
+        // the JVM passes valid pointers, even though the entry point isn't `unsafe`
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
+        #vis #unsafety extern "system" fn #name(#(#params),*) #entry_output {
+            #probe
+            #before
+            #(#convert)*
+            #call
+            #after
+        }
+    }
+}
+
+/// The argument a hook function taking a `jni::JavaVM` is called with, once `__jni_vm` has been
+/// converted by `vm_conversion`. A type called `JavaVM` may have been imported from elsewhere, like
+/// `jni::sys`, so the argument is spanned at the parameter's type to report a mismatch there.
+pub(crate) fn vm_arg(vm: VmParam, sig: &syn::Signature) -> TokenStream {
+    let span = match sig.inputs.first() {
+        Some(syn::FnArg::Typed(arg)) => Span::call_site().located_at(arg.ty.span()),
+        _ => Span::call_site(),
+    };
+    match vm {
+        VmParam::Borrowed => quote::quote_spanned! { span=> &__jni_vm },
+        _ => quote::quote_spanned! { span=> __jni_vm },
+    }
+}
+
+/// Converts the raw `__jni_vm` pointer into a `jni::JavaVM`, returning `failure` if it is null.
+pub(crate) fn vm_conversion(failure: &TokenStream) -> TokenStream {
+    quote::quote! {
+        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+            ::core::result::Result::Ok(vm) => vm,
+            ::core::result::Result::Err(_) => return #failure,
+        };
+    }
+}
+
+/// Makes a `call` to a hook function within `catch_unwind`, since unwinding out of an entry point
+/// would abort the JVM. This evaluates to the version the hook returns, or to `success` if it
+/// doesn't return one; panics and errors are logged to stderr as failures of `name`, and evaluate
+/// to `failure`.
+pub(crate) fn guarded_call(
+    call: &TokenStream,
+    output: HookOutput,
+    success: &TokenStream,
+    failure: &TokenStream,
+    name: &str,
+    span: proc_macro2::Span,
+) -> TokenStream {
+    let (value, success) = match output {
+        HookOutput::Unit | HookOutput::UnitResult => (quote::quote! { () }, success.clone()),
+        HookOutput::Version | HookOutput::VersionResult => {
+            (quote::quote! { version }, quote::quote! { version })
+        }
+    };
+    let error_message = syn::LitStr::new(&format!("{} failed: {{:?}}", name), span);
+    let returned = match output {
+        HookOutput::Unit | HookOutput::Version => quote::quote! {
+            ::core::result::Result::Ok(#value) => #success,
+        },
+        HookOutput::UnitResult | HookOutput::VersionResult => quote::quote! {
+            ::core::result::Result::Ok(::core::result::Result::Ok(#value)) => #success,
+            ::core::result::Result::Ok(::core::result::Result::Err(error)) => {
+                ::std::eprintln!(#error_message, error);
+                #failure
+            }
+        },
+    };
+    let panic_message = syn::LitStr::new(&format!("{} panicked: {{}}", name), span);
+    quote::quote! {
+        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #call)) {
+            #returned
+            ::core::result::Result::Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                    .unwrap_or("Box<dyn Any>");
+                ::std::eprintln!(#panic_message, message);
+                #failure
+            }
+        }
+    }
+}
+
+/// How a hook function takes the VM as `ty`, if it takes it as a `jni::JavaVM`.
+fn vm_param(ty: &syn::Type) -> Option<VmParam> {
+    match ty {
+        _ if is_vm(ty) => Some(VmParam::Owned),
+        syn::Type::Reference(r) if r.mutability.is_none() && is_vm(&r.elem) => {
+            Some(VmParam::Borrowed)
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether `ty` is `&str`.
+fn is_str(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Reference(r) if r.mutability.is_none() && last_segment(&r.elem).is_some_and(|ident| ident == "str"))
+}
+
+/// Returns whether `ty` is a path to a type called `JavaVM`, which is taken to be `jni::JavaVM`
+/// unless it is explicitly the `jni-sys` one.
+fn is_vm(ty: &syn::Type) -> bool {
+    last_segment(ty).is_some_and(|ident| ident == "JavaVM") && !is_sys_vm(ty)
+}
+
+/// Returns whether `ty` is spelled as `jni::sys::JavaVM` or `jni_sys::JavaVM`, or is a reference
+/// to one.
+fn is_sys_vm(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(r) => is_sys_vm(&r.elem),
+        syn::Type::Path(path) if last_segment(ty).is_some_and(|ident| ident == "JavaVM") => path
+            .path
+            .segments
+            .iter()
+            .any(|segment| segment.ident == "sys" || segment.ident == "jni_sys"),
+        _ => false,
+    }
+}
+
+/// Returns whether `ty` is a raw pointer to a type whose name is `name`.
+fn is_pointer_to(ty: &syn::Type, name: &str) -> bool {
+    match ty {
+        syn::Type::Ptr(ptr) => last_segment(&ptr.elem).is_some_and(|ident| ident == name),
+        _ => false,
+    }
+}
+
+fn last_segment(ty: &syn::Type) -> Option<&syn::Ident> {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => {
+            path.path.segments.last().map(|segment| &segment.ident)
+        }
+        _ => None,
+    }
+}
+
+fn is_unit(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Tuple(tuple) if tuple.elems.is_empty())
+}
+
+/// Returns the `Ok` type of a `Result`, or of an alias like `jni::errors::Result<T>`.
+fn result_ok_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    args.args.iter().find_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiate(
+        version: Option<&str>,
+        min: Option<&str>,
+        static_symbol: Option<&str>,
+    ) -> Result<Option<Vec<i32>>, String> {
+        let literal = |value: &str| syn::LitStr::new(value, proc_macro2::Span::call_site());
+        let args = HookArgs {
+            version: version.map(literal),
+            min: min.map(literal),
+            ..HookArgs::default()
+        };
+        Negotiation::from_args(&args, static_symbol)
+            .map(|negotiation| negotiation.map(|negotiation| negotiation.versions))
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_negotiation() {
+        assert_eq!(negotiate(None, None, None), Ok(None));
+        assert_eq!(
+            negotiate(Some("1.8"), None, None),
+            Ok(Some(vec![0x0001_0008]))
+        );
+        assert_eq!(
+            negotiate(Some("10"), Some("1.6"), None),
+            Ok(Some(vec![
+                0x000a_0000,
+                0x0009_0000,
+                0x0001_0008,
+                0x0001_0006
+            ]))
+        );
+        assert_eq!(
+            negotiate(Some("21"), Some("1.8"), Some("JNI_OnLoad_example")),
+            Ok(Some(vec![
+                0x0015_0000,
+                0x0014_0000,
+                0x0013_0000,
+                0x000a_0000,
+                0x0009_0000,
+                0x0001_0008
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_invalid_negotiation() {
+        assert_eq!(
+            negotiate(Some("1.7"), None, None),
+            Err("Unknown JNI version `1.7`; expected one of 1.1, 1.2, 1.4, 1.6, 1.8, 9, 10, 19, 20, 21, 24".to_string())
+        );
+        assert_eq!(
+            negotiate(None, Some("1.6"), None),
+            Err("`min` needs a `version` to negotiate up to".to_string())
+        );
+        assert_eq!(
+            negotiate(Some("1.6"), Some("1.8"), None),
+            Err("`min` can't be newer than `version` (\"1.6\")".to_string())
+        );
+        assert_eq!(
+            negotiate(Some("1.6"), None, Some("JNI_OnLoad_example")),
+            Err("`JNI_OnLoad_example` is called for a statically linked library, which must support at least JNI 1.8; raise the `version` to \"1.8\"".to_string())
+        );
+    }
+
+    fn check(sig: syn::Signature, export: JniExportType) -> Result<VmParam, String> {
+        check_output(sig, export, false).map(|(vm, _)| vm)
+    }
+
+    fn check_output(
+        sig: syn::Signature,
+        export: JniExportType,
+        negotiates: bool,
+    ) -> Result<(VmParam, HookOutput), String> {
+        check_hook_signature(&sig, export, export.hook_name(), negotiates)
+            .map(|signature| (signature.vm, signature.output))
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_hook_signatures() {
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(vm: JavaVM) -> jint },
+                JniExportType::OnLoad
+            ),
+            Ok(VmParam::Owned)
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(vm: &jni::JavaVM) },
+                JniExportType::OnUnload
+            ),
+            Ok(VmParam::Borrowed)
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(vm: *mut jni::sys::JavaVM, _: *mut c_void) -> jint },
+                JniExportType::OnLoad
+            ),
+            Ok(VmParam::Raw)
+        );
+    }
+
+    #[test]
+    fn test_hook_outputs() {
+        for (sig, export, negotiates, output) in [
+            (
+                syn::parse_quote! { fn f(vm: JavaVM) -> jint },
+                JniExportType::OnLoad,
+                false,
+                HookOutput::Version,
+            ),
+            (
+                syn::parse_quote! { fn f(vm: JavaVM) -> Result<jint, String> },
+                JniExportType::OnLoad,
+                false,
+                HookOutput::VersionResult,
+            ),
+            (
+                syn::parse_quote! { fn f(vm: JavaVM) },
+                JniExportType::OnLoad,
+                true,
+                HookOutput::Unit,
+            ),
+            (
+                syn::parse_quote! { fn f(vm: JavaVM) -> jni::errors::Result<()> },
+                JniExportType::OnLoad,
+                true,
+                HookOutput::UnitResult,
+            ),
+            (
+                syn::parse_quote! { fn f(vm: JavaVM) -> () },
+                JniExportType::OnUnload,
+                false,
+                HookOutput::Unit,
+            ),
+            (
+                syn::parse_quote! { fn f(vm: JavaVM) -> Result<(), Box<dyn Error>> },
+                JniExportType::OnUnload,
+                false,
+                HookOutput::UnitResult,
+            ),
+        ] {
+            assert_eq!(
+                check_output(sig, export, negotiates),
+                Ok((VmParam::Owned, output))
+            );
+        }
+    }
+
+    #[test]
+    fn test_agent_signatures() {
+        let signature = |sig: syn::Signature, export: JniExportType| {
+            check_hook_signature(&sig, export, export.hook_name(), false)
+                .map(|signature| (signature.vm, signature.options, signature.output))
+                .map_err(|e| e.to_string())
+        };
+        for export in [JniExportType::AgentOnLoad, JniExportType::AgentOnAttach] {
+            assert_eq!(
+                signature(syn::parse_quote! { fn f(vm: JavaVM) }, export),
+                Ok((VmParam::Owned, false, HookOutput::Unit))
+            );
+            assert_eq!(
+                signature(
+                    syn::parse_quote! { fn f(vm: &JavaVM, options: &str) -> Result<(), Error> },
+                    export
+                ),
+                Ok((VmParam::Borrowed, true, HookOutput::UnitResult))
+            );
+            assert_eq!(
+                signature(
+                    syn::parse_quote! { fn f(vm: *mut JavaVM, options: *mut c_char, reserved: *mut c_void) -> jint },
+                    export
+                ),
+                Ok((VmParam::Raw, false, HookOutput::Version))
+            );
+            assert_eq!(
+                signature(syn::parse_quote! { fn f(vm: JavaVM, options: String) }, export),
+                Err(format!("`{}` hooks must take the VM as `vm: jni::JavaVM` or `vm: &jni::JavaVM`, optionally followed by `options: &str`, or the raw `vm: *mut jni::sys::JavaVM, options: *mut c_char, reserved: *mut c_void` pointers", export.hook_name()))
+            );
+        }
+
+        assert_eq!(
+            signature(
+                syn::parse_quote! { fn f(vm: *mut JavaVM) },
+                JniExportType::AgentOnUnload
+            ),
+            Ok((VmParam::Raw, false, HookOutput::Unit))
+        );
+        assert_eq!(
+            signature(
+                syn::parse_quote! { fn f(vm: JavaVM, options: &str) },
+                JniExportType::AgentOnUnload
+            ),
+            Err("`agent_on_unload` hooks must take the VM as `vm: jni::JavaVM` or `vm: &jni::JavaVM`, or the raw `vm: *mut jni::sys::JavaVM` pointer".to_string())
+        );
+        assert_eq!(
+            signature(
+                syn::parse_quote! { fn f(vm: JavaVM) -> jint },
+                JniExportType::AgentOnUnload
+            ),
+            Err("`agent_on_unload` hooks can only return `()` or `Result<(), E>`".to_string())
+        );
+        // JNI hooks don't take options
+        assert!(signature(
+            syn::parse_quote! { fn f(vm: JavaVM, options: &str) -> jint },
+            JniExportType::OnLoad
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_invalid_hook_signatures() {
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(vm: jni::sys::JavaVM) -> jint },
+                JniExportType::OnLoad
+            ),
+            Err("`jni::sys::JavaVM` is a pointer to the VM's function table, not the VM; take `vm: jni::JavaVM`, or the raw `vm: *mut jni::sys::JavaVM, reserved: *mut c_void` pointers".to_string())
+        );
+        for sig in [
+            syn::parse_quote! { fn f(vm: &jni::sys::JavaVM) -> jint },
+            syn::parse_quote! { fn f(vm: &sys::JavaVM) -> jint },
+            syn::parse_quote! { fn f(vm: jni_sys::JavaVM) -> jint },
+        ] {
+            assert!(check(sig, JniExportType::OnLoad)
+                .unwrap_err()
+                .starts_with("`jni::sys::JavaVM` is a pointer"));
+        }
+        for sig in [
+            syn::parse_quote! { fn f() -> jint },
+            syn::parse_quote! { fn f(vm: &mut JavaVM) -> jint },
+            syn::parse_quote! { fn f(vm: JavaVM, reserved: *mut c_void) -> jint },
+            syn::parse_quote! { fn f(vm: *mut JavaVM) -> jint },
+        ] {
+            assert_eq!(
+                check(sig, JniExportType::OnLoad),
+                Err("`on_load` hooks must take the VM as `vm: jni::JavaVM` or `vm: &jni::JavaVM`, or the raw `vm: *mut jni::sys::JavaVM, reserved: *mut c_void` pointers".to_string())
+            );
+        }
+        assert_eq!(
+            check(syn::parse_quote! { fn f(vm: JavaVM) }, JniExportType::OnLoad),
+            Err("`on_load` hooks must return the JNI version they need as a `jint`, like `JNI_VERSION_1_8`".to_string())
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(vm: JavaVM) -> Result<(), String> },
+                JniExportType::OnLoad
+            ),
+            Err("`on_load` hooks returning `Result<(), E>` need a `version` to negotiate; add `version = \"...\"`, or return the JNI version they need as `Ok`".to_string())
+        );
+        assert_eq!(
+            check_output(
+                syn::parse_quote! { fn f(vm: JavaVM) -> Result<jint, String> },
+                JniExportType::OnLoad,
+                true
+            ),
+            Err("`on_load` hooks with a `version` can only return `()` or `Result<(), E>`, since the negotiated version is returned for them".to_string())
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(vm: JavaVM) -> jint },
+                JniExportType::OnUnload
+            ),
+            Err("`on_unload` hooks can only return `()` or `Result<(), E>`".to_string())
+        );
+    }
+}
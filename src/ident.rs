@@ -0,0 +1,504 @@
+//! Validation of Java identifiers and qualified names, following sections 3.8 and 3.9 of the Java
+//! language specification.
+//!
+//! `JavaLetter` and `JavaLetterOrDigit` are defined in terms of `Character.isJavaIdentifierStart`
+//! and `Character.isJavaIdentifierPart`. These are approximated with the Unicode `XID_Start` and
+//! `XID_Continue` properties, plus the currency symbols and connector punctuation that Java also
+//! allows. Ignorable format and control characters are rejected, even though `javac` accepts
+//! them, since they are invisible and almost certainly a mistake in a namespace.
+
+use std::ops::Range;
+
+use proc_macro2::Span;
+
+/// Keywords which can't be used as identifiers (JLS 3.9), including `_` since Java 9.
+const KEYWORDS: &[&str] = &[
+    "_",
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// Boolean and null literals, which can't be used as identifiers either (JLS 3.10).
+const LITERALS: &[&str] = &["true", "false", "null"];
+
+/// Contextual keywords which can't be used as the name of a class (`TypeIdentifier` in JLS 3.8).
+const RESTRICTED_TYPE_IDENTIFIERS: &[&str] = &["permits", "record", "sealed", "var", "yield"];
+
+/// Characters which can't appear in JVM class or method names at all (JVMS 4.2), even when
+/// written as backtick identifiers in Kotlin or Scala.
+const JVM_FORBIDDEN_CHARS: &[char] = &['.', ';', '[', ']', '/', '<', '>', ':', '\\'];
+
+/// Currency symbols (Unicode category `Sc`), which Java allows anywhere in identifiers.
+const CURRENCY_SYMBOLS: &[(char, char)] = &[
+    ('\u{24}', '\u{24}'),
+    ('\u{a2}', '\u{a5}'),
+    ('\u{58f}', '\u{58f}'),
+    ('\u{60b}', '\u{60b}'),
+    ('\u{7fe}', '\u{7ff}'),
+    ('\u{9f2}', '\u{9f3}'),
+    ('\u{9fb}', '\u{9fb}'),
+    ('\u{af1}', '\u{af1}'),
+    ('\u{bf9}', '\u{bf9}'),
+    ('\u{e3f}', '\u{e3f}'),
+    ('\u{17db}', '\u{17db}'),
+    ('\u{20a0}', '\u{20c0}'),
+    ('\u{a838}', '\u{a838}'),
+    ('\u{fdfc}', '\u{fdfc}'),
+    ('\u{fe69}', '\u{fe69}'),
+    ('\u{ff04}', '\u{ff04}'),
+    ('\u{ffe0}', '\u{ffe1}'),
+    ('\u{ffe5}', '\u{ffe6}'),
+    ('\u{11fdd}', '\u{11fe0}'),
+    ('\u{1e2ff}', '\u{1e2ff}'),
+    ('\u{1ecb0}', '\u{1ecb0}'),
+];
+
+/// Connector punctuation (Unicode category `Pc`), which Java allows anywhere in identifiers.
+const CONNECTOR_PUNCTUATION: &[(char, char)] = &[
+    ('_', '_'),
+    ('\u{203f}', '\u{2040}'),
+    ('\u{2054}', '\u{2054}'),
+    ('\u{fe33}', '\u{fe34}'),
+    ('\u{fe4d}', '\u{fe4f}'),
+    ('\u{ff3f}', '\u{ff3f}'),
+];
+
+/// What kind of name is being checked, which decides the keyword rules that apply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NameKind {
+    /// A `.`-separated package name.
+    Package,
+    /// A fully-qualified class name; its last segment may not be a restricted type identifier.
+    Class,
+    /// A method name, which is a single identifier.
+    Method,
+}
+
+/// Why a name was rejected by `check_name`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Reason {
+    /// Two separators are adjacent, or the name starts or ends with one.
+    EmptySegment,
+    /// The segment starts with a digit.
+    LeadingDigit(char),
+    /// The segment contains a character which isn't allowed at the given byte offset.
+    ForbiddenChar(char, usize),
+    /// The segment is a reserved keyword.
+    Keyword,
+    /// The segment is `true`, `false` or `null`.
+    Literal,
+    /// The class name is a contextual keyword like `var` or `record`.
+    RestrictedTypeName,
+}
+
+/// A rejected name segment, identified by its byte range within the checked name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct InvalidName {
+    pub(crate) segment: Range<usize>,
+    pub(crate) reason: Reason,
+}
+
+/// Returns whether `c` may start a Java identifier (`JavaLetter`).
+pub(crate) fn is_java_letter(c: char) -> bool {
+    unicode_ident::is_xid_start(c)
+        || in_table(CURRENCY_SYMBOLS, c)
+        || in_table(CONNECTOR_PUNCTUATION, c)
+}
+
+/// Returns whether `c` may appear after the first character of a Java identifier
+/// (`JavaLetterOrDigit`).
+pub(crate) fn is_java_letter_or_digit(c: char) -> bool {
+    unicode_ident::is_xid_continue(c) || in_table(CURRENCY_SYMBOLS, c)
+}
+
+fn in_table(table: &[(char, char)], c: char) -> bool {
+    table.iter().any(|&(start, end)| start <= c && c <= end)
+}
+
+/// Checks that `name` is a valid package, class or method name.
+///
+/// Package and class names are `.`-separated lists of identifiers; each one starts with a
+/// `JavaLetter`, continues with `JavaLetterOrDigit`s and isn't a keyword or literal, and the last
+/// segment of a class name isn't a restricted name like `var` or `record`. Non-ASCII identifiers
+/// are accepted, since `mangle_jni_name` escapes them as `_0xxxx`.
+///
+/// In `relaxed` mode, the keyword and `JavaLetter` rules are skipped, which allows names that
+/// Kotlin or Scala can declare with backticks; only characters which the JVM itself forbids are
+/// rejected.
+pub(crate) fn check_name(name: &str, kind: NameKind, relaxed: bool) -> Result<(), InvalidName> {
+    let mut start = 0;
+    let segments: Vec<&str> = match kind {
+        NameKind::Method => vec![name],
+        NameKind::Package | NameKind::Class => name.split('.').collect(),
+    };
+    let last = segments.len() - 1;
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        let range = start..start + segment.len();
+        start = range.end + 1;
+        let invalid = |reason| {
+            Err(InvalidName {
+                segment: range.clone(),
+                reason,
+            })
+        };
+
+        if segment.is_empty() {
+            return invalid(Reason::EmptySegment);
+        }
+
+        if relaxed {
+            if let Some((offset, c)) = segment
+                .char_indices()
+                .find(|&(_, c)| JVM_FORBIDDEN_CHARS.contains(&c) || c.is_control())
+            {
+                return invalid(Reason::ForbiddenChar(c, offset));
+            }
+            continue;
+        }
+
+        let first = segment.chars().next().unwrap();
+        if !is_java_letter(first) {
+            if first.is_numeric() {
+                return invalid(Reason::LeadingDigit(first));
+            }
+            return invalid(Reason::ForbiddenChar(first, 0));
+        }
+        if let Some((offset, c)) = segment
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| !is_java_letter_or_digit(c))
+        {
+            return invalid(Reason::ForbiddenChar(c, offset));
+        }
+
+        if KEYWORDS.contains(&segment) {
+            return invalid(Reason::Keyword);
+        }
+        if LITERALS.contains(&segment) {
+            return invalid(Reason::Literal);
+        }
+        if kind == NameKind::Class && i == last && RESTRICTED_TYPE_IDENTIFIERS.contains(&segment) {
+            return invalid(Reason::RestrictedTypeName);
+        }
+    }
+
+    Ok(())
+}
+
+impl InvalidName {
+    /// Describes why `name` was rejected, naming the offending segment and character.
+    pub(crate) fn describe(&self, name: &str) -> String {
+        let segment = &name[self.segment.clone()];
+        match self.reason {
+            Reason::EmptySegment => format!("`{}` contains an empty segment", name),
+            Reason::LeadingDigit(c) => {
+                format!("segment `{}` starts with the digit `{}`", segment, c)
+            }
+            Reason::ForbiddenChar(c, 0) if !c.is_whitespace() && !c.is_control() => format!(
+                "segment `{}` starts with `{}`, which can't start a Java identifier",
+                segment, c
+            ),
+            Reason::ForbiddenChar(c, _) => format!(
+                "segment `{}` contains {:?} (U+{:04X}), which isn't allowed in names",
+                segment, c, c as u32
+            ),
+            Reason::Keyword => format!(
+                "`{}` is a reserved Java keyword (add `relaxed` to allow it)",
+                segment
+            ),
+            Reason::Literal => format!(
+                "`{}` is a Java literal (add `relaxed` to allow it)",
+                segment
+            ),
+            Reason::RestrictedTypeName => format!(
+                "`{}` can't be used as a class name (add `relaxed` to allow it)",
+                segment
+            ),
+        }
+    }
+
+    /// Returns the byte range of `name` which errors should point at: the offending character if
+    /// there is one, or else the offending segment along with any adjacent separators.
+    fn range(&self, name: &str) -> Range<usize> {
+        match self.reason {
+            Reason::ForbiddenChar(c, offset) => {
+                let start = self.segment.start + offset;
+                start..start + c.len_utf8()
+            }
+            Reason::EmptySegment => {
+                self.segment.start.saturating_sub(1)..(self.segment.end + 1).min(name.len())
+            }
+            _ => self.segment.clone(),
+        }
+    }
+}
+
+/// Returns a corrected version of `name`, if a common mistake makes it invalid: surrounding
+/// whitespace, `/` or `::` separators, or stray separators at either end.
+pub(crate) fn suggest_name(name: &str, kind: NameKind, relaxed: bool) -> Option<String> {
+    let suggestion = match kind {
+        NameKind::Method => name.trim().to_string(),
+        NameKind::Package | NameKind::Class => name
+            .trim()
+            .replace("::", ".")
+            .replace('/', ".")
+            .trim_matches('.')
+            .to_string(),
+    };
+    (suggestion != name && check_name(&suggestion, kind, relaxed).is_ok()).then_some(suggestion)
+}
+
+/// Checks `name` with `check_name`, and turns any failure into an error which starts with
+/// `message`, explains the problem and suggests a fix. The error points at the offending part of
+/// `literal` when `name` was taken verbatim from it and the compiler supports sub-spans, or at
+/// `span` otherwise.
+pub(crate) fn validate_name(
+    name: &str,
+    kind: NameKind,
+    relaxed: bool,
+    message: &str,
+    span: Span,
+    literal: Option<&syn::LitStr>,
+) -> syn::Result<()> {
+    let Err(invalid) = check_name(name, kind, relaxed) else {
+        return Ok(());
+    };
+
+    let mut message = format!("{}: {}", message, invalid.describe(name));
+    if let Some(suggestion) = suggest_name(name, kind, relaxed) {
+        message.push_str(&format!("; did you mean `{}`?", suggestion));
+    }
+    let span = literal
+        .filter(|literal| literal.value() == name)
+        .and_then(|literal| literal_subspan(literal, invalid.range(name)))
+        .unwrap_or(span);
+    Err(syn::Error::new(span, message))
+}
+
+/// Returns the span of a byte range of a string literal's value. This requires a compiler which
+/// supports `Literal::subspan`, and a literal without escapes, whose value appears verbatim in the
+/// source.
+pub(crate) fn literal_subspan(literal: &syn::LitStr, range: Range<usize>) -> Option<Span> {
+    let token = literal.token();
+    let source = token.to_string();
+    // the opening quote follows an `r` and any `#`s in raw strings, which also close the literal
+    let quote = source.find('"')?;
+    let contents = source.get(quote + 1..source.len() - quote.max(1))?;
+    if contents != literal.value() {
+        return None;
+    }
+    token.subspan(quote + 1 + range.start..quote + 1 + range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(name: &str, kind: NameKind) -> Option<Reason> {
+        check_name(name, kind, false).err().map(|e| e.reason)
+    }
+
+    #[test]
+    fn test_java_letters() {
+        for c in [
+            'a', 'Z', '_', '$', 'é', 'ß', '例', '€', '£', '\u{203f}', '\u{2160}',
+        ] {
+            assert!(is_java_letter(c), "{:?} is a JavaLetter", c);
+        }
+        for c in [
+            '0', '\u{663}', '+', '-', ' ', '\u{a0}', '\u{300}', '\u{200b}', '—',
+        ] {
+            assert!(!is_java_letter(c), "{:?} isn't a JavaLetter", c);
+        }
+        for c in ['0', '9', '\u{663}', '\u{300}', 'a', '_', '$'] {
+            assert!(is_java_letter_or_digit(c), "{:?} is a JavaLetterOrDigit", c);
+        }
+        for c in ['+', '-', ' ', '\u{a0}', '\u{200b}', '.', '/'] {
+            assert!(
+                !is_java_letter_or_digit(c),
+                "{:?} isn't a JavaLetterOrDigit",
+                c
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_name() {
+        assert_eq!(reason("com.example.Foo", NameKind::Class), None);
+        assert_eq!(reason("com.example.Foo$Bar", NameKind::Class), None);
+        assert_eq!(reason("com.example.record", NameKind::Package), None);
+        assert_eq!(reason("com.var.Foo", NameKind::Class), None);
+        assert_eq!(reason("yield", NameKind::Method), None);
+        assert_eq!(reason("café", NameKind::Method), None);
+
+        assert_eq!(
+            reason("com..Foo", NameKind::Class),
+            Some(Reason::EmptySegment)
+        );
+        assert_eq!(
+            reason("com.1Foo", NameKind::Class),
+            Some(Reason::LeadingDigit('1'))
+        );
+        assert_eq!(
+            reason("foo.+bar", NameKind::Class),
+            Some(Reason::ForbiddenChar('+', 0))
+        );
+        assert_eq!(
+            reason("com.exa-mple", NameKind::Package),
+            Some(Reason::ForbiddenChar('-', 3))
+        );
+        assert_eq!(
+            reason("com.example.class", NameKind::Class),
+            Some(Reason::Keyword)
+        );
+        assert_eq!(
+            reason("com.null.Foo", NameKind::Class),
+            Some(Reason::Literal)
+        );
+        assert_eq!(
+            reason("com.example.var", NameKind::Class),
+            Some(Reason::RestrictedTypeName)
+        );
+        assert_eq!(reason("native", NameKind::Method), Some(Reason::Keyword));
+        assert_eq!(
+            reason("say.hello", NameKind::Method),
+            Some(Reason::ForbiddenChar('.', 3))
+        );
+
+        let invalid = check_name("com.example.1Foo", NameKind::Class, false).unwrap_err();
+        assert_eq!(invalid.segment, 12..16);
+    }
+
+    #[test]
+    fn test_describe() {
+        let describe = |name: &str, kind| check_name(name, kind, false).unwrap_err().describe(name);
+        assert_eq!(
+            describe("com.example.1Foo", NameKind::Class),
+            "segment `1Foo` starts with the digit `1`"
+        );
+        assert_eq!(
+            describe("com..Foo", NameKind::Class),
+            "`com..Foo` contains an empty segment"
+        );
+        assert_eq!(
+            describe("foo.+bar", NameKind::Class),
+            "segment `+bar` starts with `+`, which can't start a Java identifier"
+        );
+        assert_eq!(
+            describe("com.example.Foo ", NameKind::Class),
+            "segment `Foo ` contains ' ' (U+0020), which isn't allowed in names"
+        );
+        assert_eq!(
+            describe("com.example.class", NameKind::Class),
+            "`class` is a reserved Java keyword (add `relaxed` to allow it)"
+        );
+        assert_eq!(
+            describe("com.example.var", NameKind::Class),
+            "`var` can't be used as a class name (add `relaxed` to allow it)"
+        );
+    }
+
+    #[test]
+    fn test_suggest_name() {
+        let suggest = |name| suggest_name(name, NameKind::Class, false);
+        assert_eq!(
+            suggest("com/example/Foo").as_deref(),
+            Some("com.example.Foo")
+        );
+        assert_eq!(
+            suggest("com.example.Foo ").as_deref(),
+            Some("com.example.Foo")
+        );
+        assert_eq!(
+            suggest("com::example::Foo").as_deref(),
+            Some("com.example.Foo")
+        );
+        assert_eq!(
+            suggest(".com.example.Foo.").as_deref(),
+            Some("com.example.Foo")
+        );
+        assert_eq!(suggest("com.example.1Foo"), None);
+        assert_eq!(suggest("com.example.Foo"), None);
+        assert_eq!(
+            suggest_name(" sayHello", NameKind::Method, false).as_deref(),
+            Some("sayHello")
+        );
+    }
+
+    #[test]
+    fn test_validate_name() {
+        let literal: syn::LitStr = syn::parse_quote!("com/example/Foo");
+        let error = validate_name(
+            &literal.value(),
+            NameKind::Class,
+            false,
+            "Invalid package namespace supplied to `jni_fn` attribute",
+            literal.span(),
+            Some(&literal),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid package namespace supplied to `jni_fn` attribute: segment `com/example/Foo` contains '/' (U+002F), which isn't allowed in names; did you mean `com.example.Foo`?"
+        );
+    }
+
+    #[test]
+    fn test_check_relaxed_name() {
+        assert!(check_name("com.example.class", NameKind::Class, true).is_ok());
+        assert!(check_name("com.example.my class", NameKind::Class, true).is_ok());
+        assert!(check_name("is valid", NameKind::Method, true).is_ok());
+        assert!(check_name("com..Foo", NameKind::Class, true).is_err());
+        assert!(check_name("com.example.Foo;", NameKind::Class, true).is_err());
+        assert!(check_name("<init>", NameKind::Method, true).is_err());
+    }
+}
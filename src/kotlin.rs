@@ -0,0 +1,112 @@
+//! JVM names of Kotlin declarations, which don't always live in the class they're written in.
+//!
+//! These follow the naming rules of the Kotlin/JVM compiler, for `external fun` declarations which
+//! aren't annotated with `@JvmName`, `@file:JvmName` or `@JvmStatic`.
+
+/// Returns the file facade class which holds the top-level declarations of a Kotlin file, given
+/// the file's package and name, like `com.example.Utils` (an optional `.kt` suffix is ignored).
+///
+/// The facade is named after the file as `PackagePartClassUtils` in the Kotlin compiler names it:
+/// sanitized, with its first letter capitalized (or `_` prepended if it starts with a digit) and
+/// `Kt` appended, like `com.example.UtilsKt`.
+pub(crate) fn file_facade_class(file: &str) -> String {
+    let file = file.strip_suffix(".kt").unwrap_or(file);
+    let (package, name) = match file.rsplit_once('.') {
+        Some((package, name)) => (Some(package), name),
+        None => (None, file),
+    };
+
+    let name = sanitize(name);
+    let mut facade: String = match name.chars().next() {
+        Some(first) if !first.is_numeric() => {
+            first.to_uppercase().chain(name.chars().skip(1)).collect()
+        }
+        _ => format!("_{}", name),
+    };
+    facade.push_str("Kt");
+
+    match package {
+        Some(package) => format!("{}.{}", package, facade),
+        None => facade,
+    }
+}
+
+/// Returns the class which holds the members of the default companion object of `class`.
+pub(crate) fn companion_class(class: &str) -> String {
+    format!("{}$Companion", class)
+}
+
+/// Returns the JVM name of an `internal` Kotlin function, which is suffixed with the name of the
+/// module it is compiled in (e.g. `app_release`).
+pub(crate) fn internal_method_name(method: &str, module: &str) -> String {
+    format!("{}${}", method, sanitize(module))
+}
+
+/// Replaces every character other than a letter or a digit with `_`, including `$`, as
+/// `NameUtils.sanitizeAsJavaIdentifier` in the Kotlin compiler does. Its `\p{L}` and `\p{Digit}`
+/// classes are approximated with the Unicode `Alphabetic` and `Numeric` properties.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_facade_class() {
+        assert_eq!(
+            file_facade_class("com.example.Utils"),
+            "com.example.UtilsKt"
+        );
+        assert_eq!(
+            file_facade_class("com.example.Utils.kt"),
+            "com.example.UtilsKt"
+        );
+        assert_eq!(
+            file_facade_class("com.example.utils"),
+            "com.example.UtilsKt"
+        );
+        assert_eq!(
+            file_facade_class("com.example.my-utils"),
+            "com.example.My_utilsKt"
+        );
+        assert_eq!(file_facade_class("Main"), "MainKt");
+        assert_eq!(
+            file_facade_class("com.example.ünicode"),
+            "com.example.ÜnicodeKt"
+        );
+        // as named by kotlinc for `my-utils$v2.kt` and `2d.kt`
+        assert_eq!(
+            file_facade_class("com.example.my-utils$v2.kt"),
+            "com.example.My_utils_v2Kt"
+        );
+        assert_eq!(file_facade_class("com.example.2d"), "com.example._2dKt");
+    }
+
+    #[test]
+    fn test_companion_class() {
+        assert_eq!(
+            companion_class("com.example.Foo"),
+            "com.example.Foo$Companion"
+        );
+    }
+
+    #[test]
+    fn test_internal_method_name() {
+        assert_eq!(
+            internal_method_name("compute", "app_release"),
+            "compute$app_release"
+        );
+        assert_eq!(
+            internal_method_name("compute", "my-lib.main"),
+            "compute$my_lib_main"
+        );
+        assert_eq!(
+            internal_method_name("compute", "app$debug"),
+            "compute$app_debug"
+        );
+    }
+}
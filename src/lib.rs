@@ -9,11 +9,21 @@
 #![deny(missing_docs)]
 #![deny(unsafe_code)]
 
+mod args;
+mod container;
+mod descriptor;
+mod hook;
+mod ident;
+mod kotlin;
+mod registry;
+mod types;
+
 use proc_macro2::TokenStream;
-use quote::ToTokens;
 use syn::ItemFn;
 use syn::spanned::Spanned;
 
+use args::JniArgs;
+
 /// Enumerates supported types of JNI exports; each export type is internally set up by exported
 /// functions.
 ///
@@ -24,15 +34,46 @@ use syn::spanned::Spanned;
 /// - OnLoadStatic: The `JNI_OnLoad` function, but static.
 /// - OnUnload: The `JNI_OnUnload` function.
 /// - OnUnloadStatic: The `JNI_OnUnload` function, but static.
+/// - AgentOnLoad: The `Agent_OnLoad` function of a JVMTI agent.
+/// - AgentOnAttach: The `Agent_OnAttach` function of a JVMTI agent.
+/// - AgentOnUnload: The `Agent_OnUnload` function of a JVMTI agent.
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum JniExportType {
     OnLoad,
     OnUnload,
+    AgentOnLoad,
+    AgentOnAttach,
+    AgentOnUnload,
+}
+
+impl JniExportType {
+    /// The name of the attribute which exports the hook.
+    fn hook_name(self) -> &'static str {
+        match self {
+            JniExportType::OnLoad => "on_load",
+            JniExportType::OnUnload => "on_unload",
+            JniExportType::AgentOnLoad => "agent_on_load",
+            JniExportType::AgentOnAttach => "agent_on_attach",
+            JniExportType::AgentOnUnload => "agent_on_unload",
+        }
+    }
+
+    /// The symbol of the hook in a shared library, which is suffixed with `_<lib>` in a static one.
+    fn symbol(self) -> &'static str {
+        match self {
+            JniExportType::OnLoad => "JNI_OnLoad",
+            JniExportType::OnUnload => "JNI_OnUnload",
+            JniExportType::AgentOnLoad => "Agent_OnLoad",
+            JniExportType::AgentOnAttach => "Agent_OnAttach",
+            JniExportType::AgentOnUnload => "Agent_OnUnload",
+        }
+    }
 }
 
 /// Annotate a function with this procedural macro attribute to expose it over the JNI.
 ///
-/// This attribute takes a single string literal as an argument, specifying the package namespace
-/// this function should be placed under.
+/// This attribute takes a string literal as its first argument, specifying the package namespace
+/// this function should be placed under. The method name is taken from the function name.
 ///
 /// ```
 /// use jni::{ JNIEnv, objects::{ JClass, JString }, sys::jstring };
@@ -49,9 +90,10 @@ enum JniExportType {
 /// }
 /// ```
 ///
-/// The `sayHello` function will automatically be expanded to have the correct ABI specification
-/// and the appropriate JNI-compatible name, i.e. in this case -
-/// `Java_com_example_RustBindings_sayHello`.
+/// The `sayHello` function is left as it is, so it can still be called and tested from Rust. Next
+/// to it, the attribute generates an exported function with the correct ABI specification and the
+/// appropriate JNI-compatible name, i.e. in this case - `Java_com_example_RustBindings_sayHello`,
+/// which forwards its arguments to `sayHello`.
 ///
 /// Then it can be accessed by, for example, Kotlin code as follows:
 /// ```kotlin
@@ -65,6 +107,229 @@ enum JniExportType {
 ///     }
 /// }
 /// ```
+///
+/// Class names copied from `javap` output may also be written in the JVM's internal form, as
+/// `"com/example/RustBindings"`, or as a descriptor, as `"Lcom/example/RustBindings;"`; both are
+/// exported under the same name as `"com.example.RustBindings"`.
+///
+/// Alternatively, the namespace can be given as separate `package` and `class` options, and the
+/// Java method name can be set with `method` when it doesn't match the Rust function name. Nested
+/// classes are written as `Outer.Inner`, and are exported as `Outer$Inner`:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass };
+/// use java_native::jni;
+///
+/// // becomes `Java_com_example_Outer_00024Inner_sayHello`
+/// #[jni(package = "com.example", class = "Outer.Inner", method = "sayHello")]
+/// pub fn say_hello(_env: JNIEnv, _: JClass) {
+///     println!("Hello!");
+/// }
+/// ```
+///
+/// To keep Rust function names in `snake_case`, the `rename_all` option converts them to the
+/// Java naming convention: `"camelCase"`, `"PascalCase"`, or `"verbatim"` (the default):
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass };
+/// use java_native::jni;
+///
+/// // becomes `Java_com_example_RustBindings_sayHello`
+/// #[jni("com.example.RustBindings", rename_all = "camelCase")]
+/// pub fn say_hello(_env: JNIEnv, _: JClass) {
+///     println!("Hello!");
+/// }
+/// ```
+///
+/// The attribute can also be applied to an inline `mod` block or an inherent `impl` block, in
+/// which case every `pub fn` inside is exported under its namespace. Functions inside may have a
+/// `#[jni(...)]` attribute of their own to override the options of the block:
+///
+/// ```
+/// use java_native::jni;
+///
+/// #[jni("com.example.RustBindings", rename_all = "camelCase")]
+/// mod bindings {
+///     use jni::{ JNIEnv, objects::JClass };
+///
+///     // becomes `Java_com_example_RustBindings_sayHello`
+///     pub fn say_hello(_env: JNIEnv, _: JClass) {
+///         println!("Hello!");
+///     }
+///
+///     // becomes `Java_com_example_Other_bye`
+///     #[jni("com.example.Other", method = "bye")]
+///     pub fn say_goodbye(_env: JNIEnv, _: JClass) {
+///         println!("Goodbye!");
+///     }
+///
+///     // not exported
+///     fn helper() {}
+/// }
+/// ```
+///
+/// Functions in a block which end up with the same JNI name are reported as an error.
+///
+/// Namespaces don't have to be string literals: `env!("NAME")`,
+/// `option_env!("NAME").unwrap_or("default")` and `concat!(...)` are resolved when the attribute
+/// is expanded, so that build scripts or build flavors can choose the package. Namespaces can also
+/// be chosen by `cfg` predicates, where the first matching predicate wins and `default` (or the
+/// leading namespace), which is required, applies when none of them match:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass };
+/// use java_native::jni;
+///
+/// #[jni(concat!(option_env!("JNI_PACKAGE").unwrap_or("com.example.app"), ".Native"))]
+/// pub fn init(_env: JNIEnv, _: JClass) {}
+///
+/// #[jni(
+///     cfg(debug_assertions) = "com.example.app.debug.Native",
+///     default = "com.example.app.Native",
+/// )]
+/// pub fn close(_env: JNIEnv, _: JClass) {}
+/// ```
+///
+/// When a class has been moved, the function can be exported under its old name as well with
+/// `alias`, which may be repeated. Each alias is a small exported function which forwards its
+/// arguments to the real one:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass };
+/// use java_native::jni;
+///
+/// // becomes `Java_com_example_v2_Native_init`, and `Java_com_example_Native_init` forwards to it
+/// #[jni("com.example.v2.Native", alias = "com.example.Native")]
+/// pub fn init(_env: JNIEnv, _: JClass) {}
+/// ```
+///
+/// Kotlin `external fun` declarations are compiled into classes which differ from the one they
+/// are written in. Instead of a namespace, `kotlin_file` takes the package and name of a Kotlin
+/// file and uses the facade class holding its top-level functions, and `kotlin_companion` takes a
+/// class and uses its companion object. Functions declared `internal` have the Kotlin module name
+/// appended to their JVM name, which is set with `kotlin_internal_module`:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::{ JClass, JObject } };
+/// use java_native::jni;
+///
+/// // `external fun init()` in `com/example/Utils.kt`: `Java_com_example_UtilsKt_init`
+/// #[jni(kotlin_file = "com.example.Utils")]
+/// pub fn init(_env: JNIEnv, _: JClass) {}
+///
+/// // `companion object { external fun create() }` in class `com.example.Foo`:
+/// // `Java_com_example_Foo_00024Companion_create`
+/// #[jni(kotlin_companion = "com.example.Foo")]
+/// pub fn create(_env: JNIEnv, _: JObject) {}
+///
+/// // `internal external fun compute()` in the `app_release` module:
+/// // `Java_com_example_UtilsKt_compute_00024app_1release`
+/// #[jni(kotlin_file = "com.example.Utils", kotlin_internal_module = "app_release")]
+/// pub fn compute(_env: JNIEnv, _: JClass) {}
+/// ```
+///
+/// Class and method names must be valid Java identifiers, and can't be keywords like `class` or
+/// `native`. Kotlin and Scala can declare other names inside backticks; add the `relaxed` flag to
+/// only reject names which the JVM itself doesn't allow:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass, sys::jboolean };
+/// use java_native::jni;
+///
+/// // `external fun `is valid`(): Boolean` in Kotlin:
+/// // `Java_com_example_Checks_is_00020valid`
+/// #[jni("com.example.Checks", method = "is valid", relaxed)]
+/// pub fn is_valid(_env: JNIEnv, _: JClass) -> jboolean {
+///     1
+/// }
+/// ```
+///
+/// The generated functions are marked `#[unsafe(no_mangle)]`, which works in every edition. With
+/// the `export_name` flag, they are exported with `#[unsafe(export_name = "...")]` instead, and
+/// keep a name which doesn't clash with Rust items named like JNI symbols:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass };
+/// use java_native::jni;
+///
+/// // exported as `Java_com_example_RustBindings_init`
+/// #[jni("com.example.RustBindings", export_name)]
+/// pub fn init(_env: JNIEnv, _: JClass) {}
+/// ```
+///
+/// Parameter and return types are checked at compile time: the first parameter must be the
+/// `JNIEnv`, the second the `JClass` or `JObject` the method is called on, and the others types
+/// from `jni` or `jni-sys`, like `JString` or `jint`. Other types, like `#[repr(transparent)]`
+/// wrappers, can be allowed by naming a trait they implement as `type_bound`:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::JClass, sys::jlong };
+/// use java_native::jni;
+///
+/// /// Implemented for types with the same representation as a JNI type.
+/// pub unsafe trait JniType {}
+///
+/// #[repr(transparent)]
+/// pub struct Handle(jlong);
+/// unsafe impl JniType for Handle {}
+///
+/// #[jni("com.example.RustBindings", type_bound = "JniType")]
+/// pub fn close(_env: JNIEnv, _: JClass, handle: Handle) {}
+/// ```
+///
+/// Overloaded native methods are resolved by the JVM using a longer symbol name which includes the
+/// argument types. To export one of the overloads, pass its method descriptor as `signature`; it is
+/// checked at compile time, and its argument types are mangled into the exported name:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::{ JByteArray, JClass }, sys::jstring };
+/// use java_native::jni;
+///
+/// // becomes `Java_com_example_Codec_encode___3B`
+/// #[jni("com.example.Codec", signature = "([B)Ljava/lang/String;")]
+/// pub fn encode(mut env: JNIEnv, _: JClass, data: JByteArray) -> jstring {
+///     let data = env.convert_byte_array(&data).unwrap();
+///     env.new_string(format!("{:?}", data)).unwrap().into_raw()
+/// }
+/// ```
+///
+/// The method descriptor of every exported function is derived from its parameter and return
+/// types, and exposed as a `__JNI_SIGNATURE_<fn>` constant next to it. Parameters which are typed
+/// as a plain `JObject`, or with a type allowed by `type_bound`, can be given their Java type with
+/// `#[jtype("...")]`. If a `signature` is given as well, the types are checked against it:
+///
+/// ```
+/// use jni::{ JNIEnv, objects::{ JClass, JObject, JString }, sys::jint };
+/// use java_native::jni;
+///
+/// #[jni("com.example.Native")]
+/// pub fn count(_env: JNIEnv, _: JClass, #[jtype("java.util.List")] items: JObject, _: JString) -> jint {
+///     0
+/// }
+///
+/// assert_eq!(__JNI_SIGNATURE_count, "(Ljava/util/List;Ljava/lang/String;)I");
+/// ```
+///
+/// With the `register` flag, the function isn't exported at all. Instead, it is registered with
+/// `RegisterNatives` under its method descriptor when the library is loaded, by the entry point of
+/// the crate's `on_load` hook, which keeps the `Java_*` symbols out of the dynamic symbol table:
+///
+/// ```
+/// use jni::{ JavaVM, JNIEnv, objects::JClass, sys::jint };
+/// use java_native::{ jni, on_load };
+///
+/// #[jni("com.example.Native", register)]
+/// pub fn add(_env: JNIEnv, _: JClass, a: jint, b: jint) -> jint {
+///     a + b
+/// }
+///
+/// #[on_load(version = "1.8")]
+/// pub fn on_load(_vm: JavaVM) {}
+/// ```
+///
+/// `RegisterNatives` fails unless the descriptor matches the Java declaration exactly, so the Java
+/// types of `JObject`, `JThrowable` and other types which may hold more than one Java type must be
+/// given with `#[jtype]` or `signature`; they are an error otherwise.
 #[proc_macro_attribute]
 pub fn jni(
     attr: proc_macro::TokenStream,
@@ -81,15 +346,19 @@ pub fn jni(
 /// Libraries linked statically for JNI use must not export this symbol otherwise it may clash with
 /// other libraries loaded over JNI; in this case, use `on_load_static` instead.
 ///
-/// See also: `on_unload` and `on_unload_static`.
+/// See also: `on_unload` and `on_unload_static`, and `on_load_init` for functions which run before
+/// the hook.
 ///
-/// Note: Hook methods such as these must take exactly one parameter, called `vm`, of type `JavaVM`
-/// from the `jni` crate, and must return a `jint` indicating their supported JNI Invocation API
-/// level; the constants for the API levels are also present in `jni`.
+/// Note: Hook methods such as these must take the VM as a `jni::JavaVM`, by value or by reference,
+/// and must return a `jint` indicating their supported JNI Invocation API level; the constants for
+/// the API levels are also present in `jni`. The function is kept as it is, and an entry point with
+/// the real `JNI_OnLoad(JavaVM *vm, void *reserved)` signature is exported next to it, which
+/// returns `JNI_ERR` if the VM pointer is null.
 ///
 /// ```
 /// use java_native::on_load;
-/// use jni::sys::{jint, JNI_VERSION_1_8, JavaVM};
+/// use jni::JavaVM;
+/// use jni::sys::{jint, JNI_VERSION_1_8};
 ///
 /// #[on_load]  // becomes `JNI_OnLoad`
 /// pub fn on_load(vm: JavaVM) -> jint {
@@ -99,12 +368,80 @@ pub fn jni(
 ///
 /// // or, for static linkage
 ///
-/// #[on_load(example)]  // becomes `JNI_OnLoad_example`
+/// #[on_load(java_native)]  // becomes `JNI_OnLoad_java_native`
+/// pub fn on_load_static(vm: &JavaVM) -> jint {
+///   // ...
+///   return JNI_VERSION_1_8;
+/// }
+/// ```
+///
+/// The JVM only calls a static hook for the library it is named after, so the library name must be
+/// the `[lib] name` of the crate, like `java_native` above, or the name of its package. If the
+/// library is linked under another name, add `unchecked_libname`, as in
+/// `#[on_load(example, unchecked_libname)]`.
+///
+/// To build the same crate as a shared library and as a static one, pass `auto` instead of a
+/// library name. The hook is then exported both as `JNI_OnLoad` and as `JNI_OnLoad_<crate>`, where
+/// `<crate>` is the `[lib] name` of the crate (`CARGO_CRATE_NAME`). With a `cfg` predicate, as in
+/// `auto(feature = "static")`, only the static name is exported when it holds, and only the
+/// dynamic one otherwise:
+///
+/// ```
+/// use java_native::on_load;
+/// use jni::JavaVM;
+/// use jni::sys::{jint, JNI_VERSION_1_8};
+///
+/// #[on_load(auto(feature = "static"))]
 /// pub fn on_load(vm: JavaVM) -> jint {
 ///   // ...
 ///   return JNI_VERSION_1_8;
 /// }
 /// ```
+///
+/// Instead of returning a version, the hook can declare the versions it supports with `version`
+/// and, optionally, `min`; the newest of them that the VM supports is then found with `GetEnv` and
+/// returned for it, or `JNI_ERR` if there is none. Static libraries must support at least JNI 1.8:
+///
+/// ```
+/// use java_native::on_load;
+/// use jni::JavaVM;
+///
+/// #[on_load(java_native, version = "21", min = "1.8")]
+/// pub fn on_load(vm: JavaVM) {
+///   // ...
+/// }
+/// ```
+///
+/// Hooks may return a `Result` instead, like `Result<jint, E>`, or `Result<(), E>` with a
+/// `version`. Errors, and panics in any hook, are logged to stderr and make the hook return
+/// `JNI_ERR`, so that `System.loadLibrary` throws an `UnsatisfiedLinkError` instead of the JVM
+/// aborting:
+///
+/// ```
+/// use java_native::on_load;
+/// use jni::JavaVM;
+///
+/// #[on_load(version = "1.8")]
+/// pub fn on_load(vm: JavaVM) -> jni::errors::Result<()> {
+///   let env = vm.get_env()?;
+///   // ...
+///   Ok(())
+/// }
+/// ```
+///
+/// The raw pointers can also be taken as they are passed by the JVM:
+///
+/// ```
+/// use core::ffi::c_void;
+/// use java_native::on_load;
+/// use jni::sys::{jint, JavaVM, JNI_VERSION_1_8};
+///
+/// #[on_load]
+/// pub unsafe fn on_load(vm: *mut JavaVM, reserved: *mut c_void) -> jint {
+///   // ...
+///   return JNI_VERSION_1_8;
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn on_load(
     attr: proc_macro::TokenStream,
@@ -121,14 +458,16 @@ pub fn on_load(
 /// Libraries linked statically for JNI use must not export this symbol otherwise it may clash with
 /// other libraries loaded over JNI; in this case, use `on_unload_static` instead.
 ///
-/// See also: `on_load` and `on_load_static`.
+/// See also: `on_load` and `on_load_static`, and `on_unload_cleanup` for functions which run after
+/// the hook.
 ///
-/// Note: Hook methods such as these must take exactly one parameter, called `vm`, of type `JavaVM`
-/// from the `jni` crate.
+/// Note: Hook methods such as these take the VM like `on_load` hooks do, and can't return a value
+/// other than a `Result<(), E>`. Errors and panics are logged to stderr rather than aborting the
+/// JVM.
 ///
 /// ```
 /// use java_native::on_unload;
-/// use jni_sys::JavaVM;
+/// use jni::JavaVM;
 ///
 /// #[on_unload]  // becomes `JNI_OnUnload`
 /// pub fn on_unload(vm: JavaVM) {
@@ -137,8 +476,8 @@ pub fn on_load(
 ///
 /// // or, for static linkage
 ///
-/// #[on_unload(example)]  // becomes `JNI_OnUnload_example`
-/// pub fn on_unload(vm: JavaVM) {
+/// #[on_unload(java_native)]  // becomes `JNI_OnUnload_java_native`
+/// pub fn on_unload_static(vm: &JavaVM) {
 ///   // ...
 /// }
 /// ```
@@ -150,16 +489,173 @@ pub fn on_unload(
     jni_hook(JniExportType::OnUnload, item.into(), attr.into()).into()
 }
 
+/// Annotate a function with this procedural macro to run it when the library is loaded, before
+/// the `on_load` hook.
+///
+/// Only one crate in a library can define the `on_load` hook, but any number of the crates linked
+/// into it can register initializers, to cache class references or set up logging for example.
+/// They are collected at link time and run by the entry point of every `on_load` hook, in order of
+/// their `priority` (lower first, 0 by default) and then of their path. If one of them fails by
+/// returning an error or panicking, the error is logged to stderr, the rest are skipped, and the
+/// hook returns `JNI_ERR`.
+///
+/// Initializers take the VM like hooks do, and return `()` or `Result<(), E>`:
+///
+/// ```
+/// use java_native::{on_load, on_load_init};
+/// use jni::JavaVM;
+///
+/// #[on_load_init(priority = -10)]
+/// fn set_up_logging(_: &JavaVM) {
+///   // ...
+/// }
+///
+/// #[on_load_init]
+/// fn cache_classes(vm: &JavaVM) -> jni::errors::Result<()> {
+///   let mut env = vm.get_env()?;
+///   // ...
+///   Ok(())
+/// }
+///
+/// // in the crate which builds the library
+/// #[on_load(version = "1.8")]
+/// pub fn on_load(_: &JavaVM) {}
+/// ```
+///
+/// Initializers are only collected on ELF targets like Linux and Android, on Apple targets, and on
+/// Windows. A crate which only registers initializers is only linked if it is used, so it may need
+/// a `use example as _;` in the crate which builds the library.
+#[proc_macro_attribute]
+pub fn on_load_init(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    jni_registered(registry::Registry::Init, item.into(), attr.into()).into()
+}
+
+/// Annotate a function with this procedural macro to run it when the library is unloaded, after
+/// the `on_unload` hook.
+///
+/// Cleanups are registered and ordered like `on_load_init` functions are, and are run by the
+/// entry point of every `on_unload` hook. Errors and panics are logged to stderr, and don't stop
+/// the remaining cleanups from running.
+///
+/// ```
+/// use java_native::{on_unload, on_unload_cleanup};
+/// use jni::JavaVM;
+///
+/// #[on_unload_cleanup]
+/// fn flush_logs(_: &JavaVM) {
+///   // ...
+/// }
+///
+/// // in the crate which builds the library
+/// #[on_unload]
+/// pub fn on_unload(_: &JavaVM) {}
+/// ```
+#[proc_macro_attribute]
+pub fn on_unload_cleanup(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    jni_registered(registry::Registry::Cleanup, item.into(), attr.into()).into()
+}
+
+/// Annotate a function with this procedural macro to expose it as the `Agent_OnLoad` entry point
+/// of a JVMTI agent.
+///
+/// This method will be called by the JVM when the agent is loaded at startup with `-agentpath` or
+/// `-agentlib`, before any class is loaded.
+///
+/// Agents take the VM like JNI hooks do, optionally followed by the options passed after `=` on
+/// the command line as a `&str`, which is empty if there are none. They return the `jint` status
+/// of the agent, or nothing if they always succeed, and may wrap that in a `Result`; errors,
+/// panics and options which aren't UTF-8 are logged to stderr and make the entry point return
+/// `JNI_ERR`, which aborts the JVM's startup. The raw
+/// `vm: *mut JavaVM, options: *mut c_char, reserved: *mut c_void` pointers can be taken as well.
+///
+/// Agents linked into a static JVM take a library name, like JNI hooks do.
+///
+/// ```
+/// use java_native::agent_on_load;
+/// use jni::JavaVM;
+///
+/// #[agent_on_load]  // becomes `Agent_OnLoad`
+/// pub fn agent_on_load(vm: JavaVM, options: &str) -> jni::errors::Result<()> {
+///   // ...
+///   Ok(())
+/// }
+///
+/// // or, for static linkage
+///
+/// #[agent_on_load(java_native)]  // becomes `Agent_OnLoad_java_native`
+/// pub fn agent_on_load_static(vm: &JavaVM) {
+///   // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn agent_on_load(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    jni_hook(JniExportType::AgentOnLoad, item.into(), attr.into()).into()
+}
+
+/// Annotate a function with this procedural macro to expose it as the `Agent_OnAttach` entry
+/// point of a JVMTI agent.
+///
+/// This method will be called by the JVM when the agent is attached to a running VM, through the
+/// Attach API. It takes and returns what `agent_on_load` functions do.
+///
+/// ```
+/// use java_native::agent_on_attach;
+/// use jni::JavaVM;
+/// use jni::sys::{jint, JNI_OK};
+///
+/// #[agent_on_attach]  // becomes `Agent_OnAttach`
+/// pub fn agent_on_attach(vm: &JavaVM, options: &str) -> jint {
+///   // ...
+///   JNI_OK
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn agent_on_attach(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    jni_hook(JniExportType::AgentOnAttach, item.into(), attr.into()).into()
+}
+
+/// Annotate a function with this procedural macro to expose it as the `Agent_OnUnload` entry
+/// point of a JVMTI agent.
+///
+/// This method will be called by the JVM when the agent is unloaded, as the VM shuts down. It takes
+/// the VM like `on_unload` hooks do, or the raw `vm: *mut JavaVM` pointer, and can't return a
+/// value other than a `Result<(), E>`.
+///
+/// ```
+/// use java_native::agent_on_unload;
+/// use jni::JavaVM;
+///
+/// #[agent_on_unload]  // becomes `Agent_OnUnload`
+/// pub fn agent_on_unload(vm: JavaVM) {
+///   // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn agent_on_unload(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    jni_hook(JniExportType::AgentOnUnload, item.into(), attr.into()).into()
+}
+
 /// Same as `jni_fn2`, but for things that carry `JniExportInfo`.
 fn jni_hook(export: JniExportType, item: TokenStream, attr: TokenStream) -> TokenStream {
-    let libname = attr.to_string();
     let item_span = item.span();
 
-    let hook_name = match export {
-        JniExportType::OnLoad => "on_load",
-        JniExportType::OnUnload => "on_unload",
-    };
-    let mut function: ItemFn = match syn::parse2(item) {
+    let hook_name = export.hook_name();
+    let function: ItemFn = match syn::parse2(item) {
         Ok(f) => f,
         Err(_e) => {
             return syn::Error::new(
@@ -170,55 +666,45 @@ fn jni_hook(export: JniExportType, item: TokenStream, attr: TokenStream) -> Toke
         }
     };
 
-    // set abi to `system`
+    let args = match parse_hook_args(attr, hook_name) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error(),
+    };
+    if let (false, Some(option)) = (
+        export == JniExportType::OnLoad,
+        args.version.as_ref().or(args.min.as_ref()),
+    ) {
+        return syn::Error::new(
+            option.span(),
+            "JNI versions are only negotiated by `on_load` hooks",
+        )
+        .to_compile_error();
+    }
+
+    // the ABI is set on the entry point
     if function.sig.abi.is_some() {
         return syn::Error::new(function.sig.abi.span(), "Don't specify an ABI for JNI hook functions - the correct ABI will be added automatically").to_compile_error();
     }
-    function.sig.abi = Some(syn::Abi {
-        extern_token: Default::default(),
-        name: Some(syn::LitStr::new("system", function.sig.ident.span())),
-    });
 
-    // rewrite function name to expected hook name
-    let target: String;
-    match export {
-        JniExportType::OnLoad => {
-            if libname.is_empty() {
-                target = create_jni_hook_fn_name("JNI_OnLoad", None)
-            } else {
-                target = create_jni_hook_fn_name("JNI_OnLoad", Some(libname))
-            }
-        },
-        JniExportType::OnUnload => {
-            if libname.is_empty() {
-                target = create_jni_hook_fn_name("JNI_OnUnload", None)
-            } else {
-                target = create_jni_hook_fn_name("JNI_OnUnload", Some(libname))
-            }
-        },
+    if let Err(e) = check_exportable(&function, "JNI hook functions") {
+        return e.to_compile_error();
+    }
+    let signature = match hook::check_hook_signature(
+        &function.sig,
+        export,
+        hook_name,
+        args.version.is_some(),
+    ) {
+        Ok(signature) => signature,
+        Err(e) => return e.to_compile_error(),
     };
 
-    function.sig.ident = syn::Ident::new(
-        &target,
-        function.sig.ident.span(),
-    );
-
-    function.attrs.push(syn::Attribute {
-        pound_token: Default::default(),
-        style: syn::AttrStyle::Outer,
-        bracket_token: Default::default(),
-        meta: syn::Meta::Path(syn::parse_str("no_mangle").unwrap()),
-    });
-    function.attrs.push(syn::Attribute {
-        pound_token: Default::default(),
-        style: syn::AttrStyle::Outer,
-        bracket_token: Default::default(),
-        meta: syn::Meta::List(syn::MetaList {
-            path: syn::parse_str("allow").unwrap(),
-            delimiter: syn::MacroDelimiter::Paren(Default::default()),
-            tokens: quote::quote! { non_snake_case },
-        }),
-    });
+    // the name the hook is exported under
+    let libname = args.libname.clone();
+    let target = match libname.is_empty() {
+        true => create_jni_hook_fn_name(export.symbol(), None),
+        false => create_jni_hook_fn_name(export.symbol(), Some(libname)),
+    };
 
     if !matches!(function.vis, syn::Visibility::Public(_)) {
         return syn::Error::new(
@@ -227,156 +713,913 @@ fn jni_hook(export: JniExportType, item: TokenStream, attr: TokenStream) -> Toke
         )
             .to_compile_error();
     }
-    function.into_token_stream()
+
+    let static_symbol = match export {
+        JniExportType::OnLoad if !args.libname.is_empty() => Some(target.as_str()),
+        _ => None,
+    };
+    let negotiation = match hook::Negotiation::from_args(&args, static_symbol) {
+        Ok(negotiation) => negotiation,
+        Err(e) => return e.to_compile_error(),
+    };
+    if let (Some(symbol), None) = (static_symbol, &negotiation) {
+        if let Err(e) = hook::check_static_version(&function, symbol) {
+            return e.to_compile_error();
+        }
+    }
+
+    // `auto` exports the hook under the dynamic name as well, and `auto(...)` picks one of them
+    let mut targets = vec![(target.clone(), args.static_cfg.clone())];
+    if args.auto {
+        let dynamic_target = create_jni_hook_fn_name(export.symbol(), None);
+        let predicate = args
+            .static_cfg
+            .as_ref()
+            .map(|predicate| syn::parse_quote! { not(#predicate) });
+        targets.insert(0, (dynamic_target, predicate));
+    }
+
+    // the hook function is kept as it is, and exported through entry points with the ABI the JVM
+    // expects
+    let entry_points = targets.iter().map(|(target, predicate)| {
+        let mut entry_point =
+            hook::create_entry_point(&function, target, signature, export, negotiation.as_ref());
+        if let Some(predicate) = predicate {
+            let cfg = syn::parse_quote! { #[cfg(#predicate)] };
+            entry_point.attrs.insert(0, cfg);
+        }
+        entry_point
+    });
+    quote::quote! { #function #(#entry_points)* }
 }
 
-/// Deals exclusively with `proc_macro2::TokenStream` instead of `proc_macro::TokenStream`,
-/// allowing it and all interior functionality to be unit tested.
-fn jni_fn2(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let attr_span = attr.span();
+/// Same as `jni_hook`, but for functions which are registered to be run by the entry points of
+/// hooks rather than exported themselves.
+fn jni_registered(
+    registry: registry::Registry,
+    item: TokenStream,
+    attr: TokenStream,
+) -> TokenStream {
     let item_span = item.span();
 
-    let mut function: ItemFn = match syn::parse2(item) {
+    let attribute = registry.attribute();
+    let function: ItemFn = match syn::parse2(item) {
         Ok(f) => f,
         Err(_e) => {
             return syn::Error::new(
                 item_span,
-                "The `jni_fn` attribute can only be applied to `fn` items",
+                format!(
+                    "The `{}` attribute can only be applied to `fn` items",
+                    attribute
+                ),
             )
             .to_compile_error()
         }
     };
+    let priority = match registry::parse_priority(attr, registry) {
+        Ok(priority) => priority,
+        Err(e) => return e.to_compile_error(),
+    };
 
-    let namespace = match syn::parse2::<syn::LitStr>(attr) {
-        Ok(n) => n,
-        Err(_e) => return syn::Error::new(attr_span, "The `jni_fn` attribute must have a single string literal supplied to specify the namespace").to_compile_error(),
-    }.value();
+    let mut errors = Errors::default();
+    check_callable(
+        &function.sig,
+        &format!("`{}` functions", attribute),
+        &mut errors,
+    );
+    // registered functions return what `on_unload` hooks do
+    let signature = errors.check(hook::check_hook_signature(
+        &function.sig,
+        JniExportType::OnUnload,
+        attribute,
+        false,
+    ));
+    if let Err(e) = errors.finish() {
+        return e.to_compile_error();
+    }
+    let Some(signature) = signature else {
+        unreachable!("errors are reported above");
+    };
 
-    if !valid_namespace(&namespace) {
-        return syn::Error::new(
-            attr_span,
-            "Invalid package namespace supplied to `jni_fn` attribute",
-        )
-        .to_compile_error();
+    let registration = registry::register(&function, registry, priority, signature);
+    quote::quote! { #function #registration }
+}
+
+/// Parses the arguments of a hook attribute, and checks that the library name, if there is one,
+/// can be used in a symbol name. Dynamic hooks don't take a library name, which is returned as an
+/// empty string.
+fn parse_hook_args(attr: TokenStream, hook_name: &str) -> syn::Result<hook::HookArgs> {
+    use syn::ext::IdentExt;
+    use syn::parse::{ParseStream, Parser};
+
+    let mut args = hook::HookArgs::default();
+    let is_option = |input: ParseStream| input.peek(syn::Ident) && input.peek2(syn::Token![=]);
+    let parser = |input: ParseStream| {
+        let mut libname = None;
+        if !input.is_empty() && !is_option(input) {
+            let lookahead = input.lookahead1();
+            libname = if lookahead.peek(syn::LitStr) {
+                let literal: syn::LitStr = input.parse()?;
+                Some((literal.value(), literal.span(), Some(literal)))
+            } else if lookahead.peek(syn::Ident::peek_any) {
+                let ident = syn::Ident::parse_any(input)?;
+                if ident == "auto" {
+                    // `r#auto` and `"auto"` name a library called `auto`
+                    if input.peek(syn::token::Paren) {
+                        let predicate;
+                        syn::parenthesized!(predicate in input);
+                        args.static_cfg = Some(predicate.parse()?);
+                    }
+                    let Some(crate_name) = crate_name() else {
+                        return Err(syn::Error::new(
+                            ident.span(),
+                            "`auto` names the library after the crate, but `CARGO_CRATE_NAME` isn't set; pass the library name instead",
+                        ));
+                    };
+                    args.auto = true;
+                    Some((crate_name, ident.span(), None))
+                } else {
+                    Some((ident.unraw().to_string(), ident.span(), None))
+                }
+            } else {
+                return Err(lookahead.error());
+            };
+        }
+        while !input.is_empty() {
+            if libname.is_some()
+                || args.version.is_some()
+                || args.min.is_some()
+                || args.unchecked_libname
+            {
+                input.parse::<syn::Token![,]>()?;
+            }
+            if input.peek(syn::Ident) && !is_option(input) {
+                let flag: syn::Ident = input.fork().parse()?;
+                if flag == "unchecked_libname" {
+                    input.parse::<syn::Ident>()?;
+                    if args.unchecked_libname {
+                        return Err(syn::Error::new(
+                            flag.span(),
+                            "`unchecked_libname` may only be specified once",
+                        ));
+                    }
+                    args.unchecked_libname = true;
+                    continue;
+                }
+            }
+            if !is_option(input) {
+                return Err(input.error(format!(
+                    "The `{}` attribute takes at most one library name",
+                    hook_name
+                )));
+            }
+            let key: syn::Ident = input.parse()?;
+            let slot = match key.to_string().as_str() {
+                "version" => &mut args.version,
+                "min" => &mut args.min,
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("Unknown `{}` attribute option `{}`", hook_name, key),
+                    ))
+                }
+            };
+            if slot.is_some() {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("`{}` may only be specified once", key),
+                ));
+            }
+            input.parse::<syn::Token![=]>()?;
+            *slot = Some(input.parse()?);
+        }
+        Ok(libname)
+    };
+    let Some((libname, span, literal)) = parser.parse2(attr)? else {
+        return Ok(args);
+    };
+
+    let invalid = |range: std::ops::Range<usize>, reason: String| {
+        let mut message = format!(
+            "Invalid library name `{}` supplied to `{}`: {}",
+            libname, hook_name, reason
+        );
+        if let Some(suggestion) = suggest_hook_libname(&libname) {
+            message.push_str(&format!("; did you mean `{}`?", suggestion));
+        }
+        let span = literal
+            .as_ref()
+            .and_then(|literal| ident::literal_subspan(literal, range))
+            .unwrap_or(span);
+        Err(syn::Error::new(span, message))
+    };
+    match libname.char_indices().next() {
+        None => {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "The library name supplied to `{}` can't be empty; leave out the parentheses to export a dynamic hook",
+                    hook_name
+                ),
+            ))
+        }
+        Some((_, c)) if c.is_ascii_digit() => {
+            return invalid(0..1, format!("it starts with the digit `{}`", c))
+        }
+        Some(_) => {}
+    }
+    if let Some((offset, c)) = libname
+        .char_indices()
+        .find(|&(_, c)| !c.is_ascii_alphanumeric() && c != '_')
+    {
+        return invalid(
+            offset..offset + c.len_utf8(),
+            format!("{:?} can't appear in a symbol name", c),
+        );
+    }
+    if !args.auto && !args.unchecked_libname {
+        check_libname(&libname, span, hook_name, &library_names())?;
+    }
+    args.libname = libname;
+    Ok(args)
+}
+
+/// Checks that a static hook is named after the library it is built into, since the JVM only
+/// calls `JNI_OnLoad_<lib>` for a library called `<lib>`. Any of `names` is accepted, and nothing
+/// is checked without them.
+fn check_libname(
+    libname: &str,
+    span: proc_macro2::Span,
+    hook_name: &str,
+    names: &[String],
+) -> syn::Result<()> {
+    match names.first() {
+        Some(name) if !names.iter().any(|name| name == libname) => Err(syn::Error::new(
+            span,
+            format!(
+                "The library name `{}` supplied to `{}` isn't the name of this crate's library, `{}`, so the JVM won't call the hook; add `unchecked_libname` if the library is linked under another name",
+                libname, hook_name, name
+            ),
+        )),
+        _ => Ok(()),
     }
+}
 
-    let orig_fn_name = function.sig.ident.to_string();
+/// The names static hooks of the crate being compiled may use: its `[lib] name`, and the name of
+/// its package, which the library is named after by default. These are only known when Cargo
+/// compiles the crate.
+fn library_names() -> Vec<String> {
+    let Ok(crate_name) = std::env::var("CARGO_CRATE_NAME") else {
+        return Vec::new();
+    };
+    let mut names = vec![crate_name];
+    if let Ok(package) = std::env::var("CARGO_PKG_NAME") {
+        let package = package.replace('-', "_");
+        if !names.contains(&package) {
+            names.push(package);
+        }
+    }
+    names
+}
 
-    function.sig.ident = syn::Ident::new(
-        &create_jni_fn_name(&namespace, &orig_fn_name),
-        function.sig.ident.span(),
-    );
+/// Returns a corrected library name for a hook, if a common mistake makes it invalid: the file
+/// name of the library was given (`libexample.so`), or it contains `-` or `.` like a crate name.
+fn suggest_hook_libname(libname: &str) -> Option<String> {
+    let mut suggestion = libname.trim();
+    for extension in [".so", ".dylib", ".dll", ".a", ".lib"] {
+        if let Some(stem) = suggestion.strip_suffix(extension) {
+            suggestion = stem.strip_prefix("lib").unwrap_or(stem);
+            break;
+        }
+    }
+    let suggestion = suggestion.replace(['-', '.', ' '], "_");
+    let valid = suggestion
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_ascii_digit())
+        && suggestion
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    (valid && suggestion != libname).then_some(suggestion)
+}
 
-    function.attrs.push(syn::Attribute {
-        pound_token: Default::default(),
-        style: syn::AttrStyle::Outer,
-        bracket_token: Default::default(),
-        meta: syn::Meta::Path(syn::parse_str("no_mangle").unwrap()),
-    });
-    function.attrs.push(syn::Attribute {
-        pound_token: Default::default(),
-        style: syn::AttrStyle::Outer,
-        bracket_token: Default::default(),
-        meta: syn::Meta::List(syn::MetaList {
-            path: syn::parse_str("allow").unwrap(),
-            delimiter: syn::MacroDelimiter::Paren(Default::default()),
-            tokens: quote::quote! { non_snake_case },
-        }),
-    });
+/// Deals exclusively with `proc_macro2::TokenStream` instead of `proc_macro::TokenStream`,
+/// allowing it and all interior functionality to be unit tested.
+///
+/// Errors are collected rather than returned at the first one, and the item is always emitted so
+/// that code using it still type-checks while the errors are fixed.
+fn jni_fn2(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item_span = item.span();
+
+    let item: syn::Item = match syn::parse2(item.clone()) {
+        Ok(syn::Item::Fn(f)) => syn::Item::Fn(f),
+        Ok(syn::Item::Mod(m)) if m.content.is_some() => syn::Item::Mod(m),
+        Ok(syn::Item::Impl(i)) if i.trait_.is_none() => syn::Item::Impl(i),
+        _ => {
+            let error = syn::Error::new(
+                item_span,
+                "The `jni_fn` attribute can only be applied to `fn` items, inline `mod` blocks and inherent `impl` blocks",
+            )
+            .to_compile_error();
+            return quote::quote! { #error #item };
+        }
+    };
+
+    let mut errors = Errors::default();
+    let args = errors.check(syn::parse2::<JniArgs>(attr));
+
+    let tokens = match item {
+        syn::Item::Fn(mut function) => {
+            let shims = args
+                .as_ref()
+                .and_then(|args| {
+                    errors.check(expand_jni_fn_variants(args, &function, ExportContext::Item))
+                })
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(_, shim)| {
+                    let name = &shim.function.sig.ident;
+                    let registration = shim.registration(&quote::quote! { #name });
+                    let function = shim.function;
+                    quote::quote! { #function #registration }
+                })
+                .collect::<Vec<_>>();
+            let signature = match shims.is_empty() {
+                true => None,
+                false => args
+                    .as_ref()
+                    .and_then(|args| create_signature_const(args, &function)),
+            };
+            types::strip_jtype_attrs(&mut function.sig);
+            allow_non_snake_case(&function.sig.ident, &mut function.attrs);
+            quote::quote! { #function #signature #(#shims)* }
+        }
+        syn::Item::Mod(module) => container::expand_jni_mod(args.as_ref(), module, &mut errors),
+        syn::Item::Impl(block) => container::expand_jni_impl(args.as_ref(), block, &mut errors),
+        _ => unreachable!(),
+    };
+    let env_dependencies = args.map(|args| args.env_dependencies());
+    let errors = errors.to_compile_error();
+    quote::quote! { #errors #env_dependencies #tokens }
+}
+
+/// Creates the exported shims of a function once for every `cfg`-dependent namespace in `args`,
+/// with each set gated behind its `cfg` predicate. Without `cfg` namespaces, this is the same as
+/// `expand_jni_fn`.
+fn expand_jni_fn_variants(
+    args: &JniArgs,
+    function: &ItemFn,
+    context: ExportContext,
+) -> syn::Result<Vec<(Option<TokenStream>, Shim)>> {
+    let mut variants = Vec::new();
+    for (predicate, args) in args.cfg_variants()? {
+        for mut expanded in expand_jni_fn(&args, function, context)? {
+            if let Some(predicate) = &predicate {
+                let cfg: syn::Attribute = syn::parse_quote!(#[cfg(#predicate)]);
+                expanded.function.attrs.insert(0, cfg);
+            }
+            variants.push((predicate.clone(), expanded));
+        }
+    }
+    Ok(variants)
+}
+
+/// A function generated to make a Rust function callable from Java, which only forwards its
+/// arguments to it.
+struct Shim {
+    function: ItemFn,
+    /// The Java method the shim implements, if it is registered with `RegisterNatives` rather than
+    /// exported.
+    native: Option<registry::NativeMethod>,
+}
+
+impl Shim {
+    /// Returns the registration of the shim's native method, which refers to the shim by `path`,
+    /// or nothing if the shim is exported.
+    fn registration(&self, path: &TokenStream) -> Option<TokenStream> {
+        self.native
+            .as_ref()
+            .map(|native| registry::register_native(&self.function, path, native))
+    }
+}
+
+/// Checks that a function can be exported according to the resolved `jni` attribute arguments,
+/// and creates the shims which export it: one under the JNI name of the Java method, followed by
+/// one for each `alias`. The shims only forward their arguments to the function, which is left
+/// as it was written so that it can still be called and tested from Rust.
+///
+/// With `register`, the shims aren't exported but registered with `RegisterNatives` instead.
+fn expand_jni_fn(
+    args: &JniArgs,
+    function: &ItemFn,
+    context: ExportContext,
+) -> syn::Result<Vec<Shim>> {
+    let mut errors = Errors::default();
+
+    let namespace = errors.check(args.namespace().and_then(|namespace| {
+        ident::validate_name(
+            &namespace.value,
+            ident::NameKind::Class,
+            args.relaxed,
+            "Invalid package namespace supplied to `jni_fn` attribute",
+            namespace.span,
+            namespace.literal.as_ref(),
+        )?;
+        Ok(namespace.value)
+    }));
+
+    let orig_fn_name = errors.check(args.method_name(&function.sig.ident));
+
+    let descriptor = errors.check(method_descriptor(args, &function.sig));
+    // only an explicit `signature` selects the long name, which is meant for overloads
+    let descriptor_args = match (&args.signature, &descriptor) {
+        (Some(_), Some(Some(descriptor))) => descriptor::parse_method_descriptor(descriptor)
+            .ok()
+            .map(|descriptor| descriptor.args.to_string()),
+        _ => None,
+    };
+
+    let alias_namespaces: Vec<String> = args
+        .aliases
+        .iter()
+        .filter_map(|alias| {
+            errors.check(alias.class_name().and_then(|alias_namespace| {
+                ident::validate_name(
+                    &alias_namespace,
+                    ident::NameKind::Class,
+                    args.relaxed,
+                    "Invalid package namespace supplied as `alias` to `jni_fn` attribute",
+                    alias.span(),
+                    alias.literal(),
+                )?;
+                Ok(alias_namespace)
+            }))
+        })
+        .collect();
+
+    if args.register && args.export_name {
+        errors.push(syn::Error::new(
+            function.sig.ident.span(),
+            "`export_name` can't be combined with `register`, since registered functions aren't exported",
+        ));
+    }
 
     if function.sig.abi.is_some() {
-        return syn::Error::new(function.sig.abi.span(), "Don't specify an ABI for `jni_fn` attributed functions - the correct ABI will be added automatically").to_compile_error();
+        errors.push(syn::Error::new(function.sig.abi.span(), "Don't specify an ABI for `jni_fn` attributed functions - the correct ABI will be added automatically"));
     }
-    function.sig.abi = Some(syn::Abi {
-        extern_token: Default::default(),
-        name: Some(syn::LitStr::new("system", function.sig.ident.span())),
-    });
 
     if !matches!(function.vis, syn::Visibility::Public(_)) {
-        return syn::Error::new(
+        errors.push(syn::Error::new(
             function.vis.span(),
             "`jni_fn` attributed functions must have public visibility (`pub`)",
-        )
-        .to_compile_error();
+        ));
     }
 
-    function.into_token_stream()
-}
+    errors.check(check_exportable(function, "`jni_fn` attributed functions"));
 
-/// Ensures that `namespace` appears roughly like a valid package name.
-///
-/// A package name is a '.'-separated identifier list.
-///
-/// Identifiers are described in section 3.8 of the Java language specification, although some
-/// JVM-compatible languages have slightly different restrictions on what is considered a valid
-/// identifier. This function attempts to catch obviously incorrect strings.
-///
-/// Please submit an issue report or patch to make this more permissive if it's required for
-/// valid JVM code! Otherwise, making it more restrictive is appreciated as long as it's confirmed
-/// to work with multiple JVM-compatible languages.
-fn valid_namespace(namespace: &str) -> bool {
-    /// These shouldn't occur _anywhere_ in the package name.
-    const FORBIDDEN_CHARS: &[char] = &[
-        ' ', ',', ':', ';', '|', '\\', '/', '!', '@', '#', '%', '^', '&', '*', '(', ')', '{', '}',
-        '[', ']', '-', '`', '~', '\t', '\n', '\r',
-    ];
+    let type_bound = match &args.type_bound {
+        Some(bound) => errors.check(bound.parse::<syn::Path>().map_err(|_| {
+            syn::Error::new(
+                bound.span(),
+                "Expected the path of a trait as `type_bound`, like `\"crate::JniType\"`",
+            )
+        })),
+        None => None,
+    };
+    // an invalid bound is reported above, so the types it would cover aren't reported as well
+    let custom_types = errors.check(types::check_signature(
+        &function.sig,
+        args.type_bound.is_some(),
+    ));
 
-    for c in FORBIDDEN_CHARS {
-        if namespace.contains(*c) {
-            return false;
+    errors.finish()?;
+    let (Some(namespace), Some(orig_fn_name), Some(custom_types), Some(descriptor)) =
+        (namespace, orig_fn_name, custom_types, descriptor)
+    else {
+        unreachable!("errors are reported above");
+    };
+    let jni_fn_name_in = |namespace: &str| match &descriptor_args {
+        Some(descriptor_args) => create_jni_long_fn_name(namespace, &orig_fn_name, descriptor_args),
+        None => create_jni_fn_name(namespace, &orig_fn_name),
+    };
+    let jni_fn_name = jni_fn_name_in(&namespace);
+    let alias_fn_names: Vec<String> = alias_namespaces
+        .iter()
+        .map(|alias_namespace| jni_fn_name_in(alias_namespace))
+        .collect();
+
+    let style = match (args.register, args.export_name) {
+        (true, _) => SymbolStyle::Registered,
+        (false, true) => SymbolStyle::ExportName,
+        (false, false) => SymbolStyle::NoMangle,
+    };
+    let mut shims: Vec<Shim> = std::iter::once((&namespace, &jni_fn_name))
+        .chain(alias_namespaces.iter().zip(&alias_fn_names))
+        .map(|(namespace, symbol)| Shim {
+            function: create_forwarder(function, symbol, style, context),
+            native: args.register.then(|| registry::NativeMethod {
+                class: namespace.replace('.', "/"),
+                name: orig_fn_name.clone(),
+                descriptor: descriptor
+                    .clone()
+                    .expect("the descriptor of registered methods is required"),
+            }),
+        })
+        .collect();
+    if let (Some(bound), false) = (&type_bound, custom_types.is_empty()) {
+        // asserted in the shims, which have the function's generics in scope
+        let assertions = custom_types.iter().map(|ty| {
+            quote::quote_spanned! {ty.span()=> __jni_type_bound::<#ty>(); }
+        });
+        let assertion: syn::Stmt = syn::parse_quote! {
+            {
+                fn __jni_type_bound<T: #bound>() {}
+                #(#assertions)*
+            }
+        };
+        for shim in &mut shims {
+            shim.function.block.stmts.insert(0, assertion.clone());
         }
     }
+    Ok(shims)
+}
+
+/// Returns the method descriptor of a function to be exported: the `signature` given in `args`,
+/// which is checked against the function's types, or else the one derived from these types, if
+/// they tell. Registered methods must have a descriptor.
+fn method_descriptor(args: &JniArgs, sig: &syn::Signature) -> syn::Result<Option<String>> {
+    let Some(signature) = &args.signature else {
+        return types::method_descriptor(sig, None, args.register);
+    };
+    let value = signature.value();
+    let explicit = descriptor::parse_method_descriptor(&value).map_err(|reason| {
+        syn::Error::new(
+            signature.span(),
+            format!(
+                "Invalid method descriptor supplied as `signature`: {}",
+                reason
+            ),
+        )
+    })?;
+    types::method_descriptor(sig, Some((signature, &explicit)), args.register)
+}
 
-    fn is_valid_ident(ident: &str) -> bool {
-        /// These shouldn't occur as the first character of an identifier.
-        const FORBIDDEN_START_CHARS: &[char] = &['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+/// Creates the `__JNI_SIGNATURE_<fn>` constant, which holds the method descriptor of an exported
+/// function, to be placed next to the function; nothing is created if the descriptor isn't known.
+/// Errors in the descriptor are reported by `expand_jni_fn`.
+fn create_signature_const(args: &JniArgs, function: &ItemFn) -> Option<TokenStream> {
+    use syn::ext::IdentExt;
 
-        if ident.is_empty() {
-            return false;
-        }
+    let descriptor = method_descriptor(args, &function.sig).ok()??;
+    let name = function.sig.ident.unraw();
+    let const_name = quote::format_ident!("__JNI_SIGNATURE_{}", name, span = name.span());
+    let doc = format!("The JVM method descriptor of `{}`.", name);
+    let attrs = function
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"));
+    let vis = &function.vis;
+    Some(quote::quote! {
+        #(#attrs)*
+        #[doc = #doc]
+        #[allow(non_upper_case_globals)]
+        #vis const #const_name: &str = #descriptor;
+    })
+}
+
+/// Adds `#[allow(non_snake_case)]` to an exported function whose name follows the Java convention,
+/// like `sayHello`, which is common since it used to be the name of the exported symbol itself.
+fn allow_non_snake_case(ident: &syn::Ident, attrs: &mut Vec<syn::Attribute>) {
+    use syn::ext::IdentExt;
+
+    // the same rule as rustc's `non_snake_case` lint
+    let name = ident.unraw().to_string();
+    let mut allow_underscore = true;
+    let snake_case = name.trim_matches('_').chars().all(|c| {
+        let allowed = match c {
+            '_' => allow_underscore,
+            c => !c.is_uppercase(),
+        };
+        allow_underscore = c != '_';
+        allowed
+    });
+    if !snake_case {
+        attrs.push(syn::parse_quote!(#[allow(non_snake_case)]));
+    }
+}
+
+/// Rejects functions which can't be called by the JVM through a single exported symbol, and those
+/// which are already exported by another attribute. `subject` names the kind of function in
+/// errors, like "JNI hook functions".
+fn check_exportable(function: &ItemFn, subject: &str) -> syn::Result<()> {
+    let mut errors = Errors::default();
+    check_callable(&function.sig, subject, &mut errors);
 
-        for c in FORBIDDEN_START_CHARS {
-            if ident.starts_with(*c) {
-                return false;
+    for attr in &function.attrs {
+        // `#[unsafe(no_mangle)]` and `#[unsafe(export_name = "...")]` are checked as well
+        let path = match &attr.meta {
+            syn::Meta::List(list) if list.path.is_ident("unsafe") => {
+                match list.parse_args::<syn::Meta>() {
+                    Ok(meta) => meta.path().clone(),
+                    Err(_) => continue,
+                }
             }
+            meta => meta.path().clone(),
+        };
+        if let Some(name) = ["no_mangle", "export_name"]
+            .into_iter()
+            .find(|name| path.is_ident(name))
+        {
+            errors.push(syn::Error::new(
+                attr.span(),
+                format!(
+                    "Remove the `{}` attribute; {} are exported under the right name automatically",
+                    name, subject
+                ),
+            ));
+        }
+    }
+    errors.finish()
+}
+
+/// Rejects signatures which can't be called through a function pointer of a single type, like
+/// `async` and generic functions.
+fn check_callable(sig: &syn::Signature, subject: &str, errors: &mut Errors) {
+    if let Some(asyncness) = &sig.asyncness {
+        errors.push(syn::Error::new(
+            asyncness.span(),
+            format!(
+                "{} can't be `async`, since the JVM calls them synchronously; block on the future inside the function instead",
+                subject
+            ),
+        ));
+    }
+    if let Some(constness) = &sig.constness {
+        errors.push(syn::Error::new(
+            constness.span(),
+            format!("{} can't be `const`", subject),
+        ));
+    }
+    for param in &sig.generics.params {
+        let name = match param {
+            syn::GenericParam::Lifetime(_) => continue,
+            syn::GenericParam::Type(param) => &param.ident,
+            syn::GenericParam::Const(param) => &param.ident,
+        };
+        errors.push(syn::Error::new(
+            param.span(),
+            format!(
+                "{} can't be generic over `{}`, since only a single instantiation could be exported; only lifetime parameters are allowed",
+                subject, name
+            ),
+        ));
+    }
+    if let Some(variadic) = &sig.variadic {
+        errors.push(syn::Error::new(
+            variadic.span(),
+            format!("{} can't be variadic", subject),
+        ));
+    }
+}
+
+/// Collects the errors found while expanding an item, so that they are all reported at once
+/// instead of one per build.
+#[derive(Default)]
+pub(crate) struct Errors(Option<syn::Error>);
+
+impl Errors {
+    pub(crate) fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
         }
+    }
+
+    /// Records the error of `result`, if there is one, and returns its value otherwise.
+    pub(crate) fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    pub(crate) fn finish(self) -> syn::Result<()> {
+        self.0.map_or(Ok(()), Err)
+    }
+
+    pub(crate) fn to_compile_error(&self) -> TokenStream {
+        self.0
+            .as_ref()
+            .map(syn::Error::to_compile_error)
+            .unwrap_or_default()
+    }
+}
+
+/// Where an exported function is defined, which decides how generated functions refer to it.
+#[derive(Clone, Copy)]
+enum ExportContext {
+    /// A free function, at the top level or inside a `mod` block.
+    Item,
+    /// An associated function inside an `impl` block.
+    ImplBlock,
+}
+
+/// How an exported function is given its symbol name.
+#[derive(Clone, Copy)]
+enum SymbolStyle {
+    /// The function is named after the symbol, and marked `#[unsafe(no_mangle)]`.
+    NoMangle,
+    /// The function keeps a Rust-style name, and is marked `#[unsafe(export_name = "...")]`.
+    ExportName,
+    /// The function isn't exported, but registered with `RegisterNatives`; it is private, and
+    /// named after the symbol it would have been exported as.
+    Registered,
+}
 
-        true
+/// The attributes added to every exported function, which export it as `symbol`. These use the
+/// `unsafe(...)` form, which is required in edition 2024 and accepted by all editions since Rust
+/// 1.82.
+fn export_attrs(symbol: &str, style: SymbolStyle) -> [syn::Attribute; 2] {
+    let export = match style {
+        SymbolStyle::NoMangle => quote::quote! { no_mangle },
+        SymbolStyle::ExportName => quote::quote! { export_name = #symbol },
+        SymbolStyle::Registered => unreachable!("registered functions aren't exported"),
+    };
+    [
+        syn::Attribute {
+            pound_token: Default::default(),
+            style: syn::AttrStyle::Outer,
+            bracket_token: Default::default(),
+            meta: syn::Meta::List(syn::MetaList {
+                path: syn::Ident::new("unsafe", proc_macro2::Span::call_site()).into(),
+                delimiter: syn::MacroDelimiter::Paren(Default::default()),
+                tokens: export,
+            }),
+        },
+        syn::Attribute {
+            pound_token: Default::default(),
+            style: syn::AttrStyle::Outer,
+            bracket_token: Default::default(),
+            meta: syn::Meta::List(syn::MetaList {
+                path: syn::parse_str("allow").unwrap(),
+                delimiter: syn::MacroDelimiter::Paren(Default::default()),
+                tokens: quote::quote! { non_snake_case },
+            }),
+        },
+    ]
+}
+
+/// Prefixed to the symbol to name functions exported with `SymbolStyle::ExportName`.
+const EXPORT_NAME_PREFIX: &str = "__export_";
+
+/// Prefixed to the symbol to name functions created with `SymbolStyle::Registered`.
+const REGISTERED_PREFIX: &str = "__jni_native_";
+
+/// Returns the symbol that a function created by `create_forwarder` is exported as, or would be
+/// exported as if it is registered instead.
+fn forwarder_symbol(forwarder: &ItemFn) -> String {
+    let name = forwarder.sig.ident.to_string();
+    match [EXPORT_NAME_PREFIX, REGISTERED_PREFIX]
+        .into_iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+    {
+        Some(symbol) => symbol.to_string(),
+        None => name,
     }
+}
 
-    for ident in namespace.split('.') {
-        if !is_valid_ident(ident) {
-            return false;
+/// Creates an `extern "system"` function exported as `symbol`, with the same arguments as
+/// `target`, which only forwards them to `target`. Any `cfg` attributes of `target` are copied,
+/// so that the function is only exported when `target` exists. With `SymbolStyle::Registered`, the
+/// function is private and isn't exported.
+fn create_forwarder(
+    target: &ItemFn,
+    symbol: &str,
+    style: SymbolStyle,
+    context: ExportContext,
+) -> ItemFn {
+    let name = match style {
+        SymbolStyle::NoMangle => symbol.to_string(),
+        SymbolStyle::ExportName => format!("{}{}", EXPORT_NAME_PREFIX, symbol),
+        SymbolStyle::Registered => format!("{}{}", REGISTERED_PREFIX, symbol),
+    };
+    let mut sig = target.sig.clone();
+    sig.ident = syn::Ident::new(&name, target.sig.ident.span());
+    sig.abi = Some(syn::Abi {
+        extern_token: Default::default(),
+        name: Some(syn::LitStr::new("system", target.sig.ident.span())),
+    });
+
+    let mut arg_names = Vec::new();
+    for (i, input) in sig.inputs.iter_mut().enumerate() {
+        if let syn::FnArg::Typed(arg) = input {
+            let arg_name = quote::format_ident!("__jni_arg{}", i);
+            arg.attrs.clear();
+            *arg.pat = syn::parse_quote!(#arg_name);
+            arg_names.push(arg_name);
         }
     }
 
-    true
+    let target_name = &target.sig.ident;
+    let call = match context {
+        ExportContext::Item => quote::quote! { #target_name(#(#arg_names),*) },
+        ExportContext::ImplBlock => quote::quote! { Self::#target_name(#(#arg_names),*) },
+    };
+    let block = match sig.unsafety {
+        Some(_) => syn::parse_quote!({ unsafe { #call } }),
+        None => syn::parse_quote!({ #call }),
+    };
+
+    let mut attrs: Vec<syn::Attribute> = target
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .cloned()
+        .collect();
+    let vis = match style {
+        SymbolStyle::Registered => {
+            attrs.push(syn::parse_quote!(#[allow(non_snake_case)]));
+            syn::Visibility::Inherited
+        }
+        _ => {
+            attrs.extend(export_attrs(symbol, style));
+            target.vis.clone()
+        }
+    };
+    ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    }
 }
 
 /// Creates a JNI-compatible function name from the given namespace and function name.
 /// This does _not_ transform the provided function name into `snakeCase` if it's not already; but
 /// `#[allow(non_snake_case)]` should be added to prevent errors.
 ///
-/// Any underscores in the original namespace or function name need to be replaced by "_1", and
-/// then dot separators need to be turned into underscores. Scala may use dollar signs in class
-/// names; those also need to be converted to `_00024`.
+/// The namespace is converted to its JVM internal form (`.` becomes `/`), and then both parts are
+/// escaped with `mangle_jni_name`; for example, underscores become `_1`, and the `$` used by
+/// Scala and for nested classes becomes `_00024`.
 fn create_jni_fn_name(namespace: &str, fn_name: &str) -> String {
-    let namespace_underscored = namespace
-        .replace('_', "_1")
-        .replace('.', "_")
-        .replace('$', "_00024");
-    let fn_name_underscored = fn_name.replace('_', "_1");
-    format!("Java_{}_{}", namespace_underscored, fn_name_underscored)
+    let namespace_mangled = mangle_jni_name(&namespace.replace('.', "/"));
+    let fn_name_mangled = mangle_jni_name(fn_name);
+    format!("Java_{}_{}", namespace_mangled, fn_name_mangled)
+}
+
+/// Creates the long form of a JNI-compatible function name, which is used to tell overloaded
+/// native methods apart. `args` holds the argument types of the method descriptor, without the
+/// surrounding parentheses, and is appended to the short name after a double underscore.
+fn create_jni_long_fn_name(namespace: &str, fn_name: &str, args: &str) -> String {
+    format!(
+        "{}__{}",
+        create_jni_fn_name(namespace, fn_name),
+        mangle_jni_name(args)
+    )
+}
+
+/// Escapes a class name (in internal, `/`-separated form), method name or argument signature as
+/// described in the "Resolving Native Method Names" section of the JNI specification.
+///
+/// ASCII letters and digits are kept as-is, and `/` becomes `_`. The escape sequences are:
+///
+/// - `_0xxxx`: the UTF-16 code unit `xxxx` (lowercase hex), used for every other character;
+///   characters outside the BMP are written as two escapes, one per surrogate.
+/// - `_1`: the character `_`.
+/// - `_2`: the character `;` in signatures.
+/// - `_3`: the character `[` in signatures.
+fn mangle_jni_name(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => mangled.push(c),
+            '/' => mangled.push('_'),
+            '_' => mangled.push_str("_1"),
+            ';' => mangled.push_str("_2"),
+            '[' => mangled.push_str("_3"),
+            _ => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    mangled.push_str(&format!("_0{:04x}", unit));
+                }
+            }
+        }
+    }
+    mangled
+}
+
+/// The name of the crate being compiled, which is its `[lib] name` when it is a library. Outside
+/// of a build, the name of the package is used, which is the default name of its library.
+fn crate_name() -> Option<String> {
+    std::env::var("CARGO_CRATE_NAME").ok().or_else(|| {
+        std::env::var("CARGO_PKG_NAME")
+            .ok()
+            .map(|name| name.replace('-', "_"))
+    })
 }
 
 /// Creates a function name for a JNI hook function, like `JNI_OnLoad` or `JNI_OnUnload`; these
 /// functions are expected to be exported at the root level of the shared or static object.
 fn create_jni_hook_fn_name(prefix: &str, postfix: Option<String>) -> String {
-    if postfix.is_some() {
-        // trim quotes if present
-        let libname = postfix.unwrap();
-        let libname = libname.trim_matches('"');
-        format!("{}_{}", prefix, libname).to_string()
+    if let Some(libname) = postfix {
+        format!("{}_{}", prefix, libname)
     } else {
         prefix.to_string()
     }
@@ -413,17 +1656,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_create_jni_long_fn_name() {
+        assert_eq!(
+            create_jni_long_fn_name("com.example.Codec", "encode", "[B"),
+            "Java_com_example_Codec_encode___3B"
+        );
+        assert_eq!(
+            create_jni_long_fn_name("com.example.Codec", "encode", "Ljava/lang/String;"),
+            "Java_com_example_Codec_encode__Ljava_lang_String_2"
+        );
+        assert_eq!(
+            create_jni_long_fn_name("com.example.Foo", "f", "ILjava/lang/String;"),
+            "Java_com_example_Foo_f__ILjava_lang_String_2"
+        );
+        assert_eq!(
+            create_jni_long_fn_name("com.example.Foo", "no_args", ""),
+            "Java_com_example_Foo_no_1args__"
+        );
+    }
+
+    fn valid_namespace(namespace: &str, relaxed: bool) -> bool {
+        ident::check_name(namespace, ident::NameKind::Class, relaxed).is_ok()
+    }
+
     #[test]
     fn test_valid_namespace() {
-        assert!(valid_namespace("com.example.Foo"));
-        assert!(valid_namespace("com.antonok.kb"));
-        assert!(valid_namespace("org.signal.client.internal.Native"));
-        assert!(valid_namespace("net.under_score"));
-        assert!(valid_namespace("a.b.c.Test$"));
-        assert!(!valid_namespace("com example Foo"));
-        assert!(!valid_namespace(" com.example.Foo"));
-        assert!(!valid_namespace("com.example.Foo "));
-        assert!(!valid_namespace("com.example.1Foo"));
+        assert!(valid_namespace("com.example.Foo", false));
+        assert!(valid_namespace("com.antonok.kb", false));
+        assert!(valid_namespace("org.signal.client.internal.Native", false));
+        assert!(valid_namespace("net.under_score", false));
+        assert!(valid_namespace("a.b.c.Test$", false));
+        assert!(!valid_namespace("com example Foo", false));
+        assert!(!valid_namespace(" com.example.Foo", false));
+        assert!(!valid_namespace("com.example.Foo ", false));
+        assert!(!valid_namespace("com.example.1Foo", false));
+        assert!(valid_namespace("com.exämple.Café", false));
+        assert!(valid_namespace("org.例子.测试", false));
+        assert!(valid_namespace("math.\u{1d4b3}", false));
+        assert!(!valid_namespace("com.example.Foo\u{a0}", false));
+        assert!(!valid_namespace("com.example.\u{0663}Foo", false));
+        assert!(!valid_namespace("com.example.Foo\u{2014}Bar", false));
+        assert!(!valid_namespace("foo.+bar", false));
+        assert!(!valid_namespace("com.example.class", false));
+        assert!(!valid_namespace("com.null.Foo", false));
+        assert!(!valid_namespace("com.example.var", false));
+        assert!(valid_namespace("com.var.Foo", false));
+        assert!(valid_namespace("com.example.class", true));
+        assert!(valid_namespace("com.example.Is Valid", true));
+        assert!(!valid_namespace("com.example.Foo;", true));
+        assert!(!valid_namespace("com..Foo", true));
+    }
+
+    #[test]
+    fn test_mangle_jni_name() {
+        assert_eq!(mangle_jni_name("com/example/Foo"), "com_example_Foo");
+        assert_eq!(mangle_jni_name("close_it"), "close_1it");
+        assert_eq!(mangle_jni_name("Ljava/lang/String;"), "Ljava_lang_String_2");
+        assert_eq!(mangle_jni_name("[B"), "_3B");
+        assert_eq!(mangle_jni_name("Outer$Inner"), "Outer_00024Inner");
+        assert_eq!(mangle_jni_name("é"), "_000e9");
+        assert_eq!(mangle_jni_name("\u{1d4b3}"), "_0d835_0dcb3");
+    }
+
+    /// Symbols produced by `javac -h` for the given class and method names.
+    #[test]
+    fn test_create_jni_fn_name_conformance() {
+        #[rustfmt::skip]
+        const CONFORMANCE: &[(&str, &str, &str)] = &[
+            ("HelloWorld", "hello", "Java_HelloWorld_hello"),
+            ("com.example.Foo", "init", "Java_com_example_Foo_init"),
+            ("com.example.Foo", "close_it", "Java_com_example_Foo_close_1it"),
+            ("net.under_score.Foo", "bar", "Java_net_under_1score_Foo_bar"),
+            ("com.example.Outer$Inner", "run", "Java_com_example_Outer_00024Inner_run"),
+            ("com.example.Foo", "$init", "Java_com_example_Foo__00024init"),
+            ("pkg.Café", "größe", "Java_pkg_Caf_000e9_gr_000f6_000dfe"),
+            ("org.例子.测试", "方法", "Java_org__04f8b_05b50__06d4b_08bd5__065b9_06cd5"),
+            ("math.Sym", "\u{1d4b3}", "Java_math_Sym__0d835_0dcb3"),
+            ("com.example.Ünïcode_Klass", "get_ß", "Java_com_example__000dcn_000efcode_1Klass_get_1_000df"),
+        ];
+
+        for (class, method, symbol) in CONFORMANCE {
+            assert_eq!(
+                create_jni_fn_name(class, method),
+                *symbol,
+                "mangling {}.{}",
+                class,
+                method
+            );
+        }
     }
 
     #[test]
@@ -444,23 +1765,72 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub extern "system" fn Java_com_example_Bar_close_1it (env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                    pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
                         unimplemented!()
                     }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
                 }
             )
         );
     }
 
     #[test]
-    fn test_unsafe_fn() {
-        let attr = quote::quote! {
-            "com.example.Bar"
+    fn test_internal_class_names() {
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+        let expected = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+            #[doc = "The JVM method descriptor of `close_it`."]
+            #[allow(non_upper_case_globals)]
+            pub const __JNI_SIGNATURE_close_it: &str = "()V";
+            #[unsafe(no_mangle)]
+            #[allow(non_snake_case)]
+            pub extern "system" fn Java_com_example_Bar_00024Inner_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                close_it(__jni_arg0, __jni_arg1)
+            }
         };
+
+        for attr in [
+            quote::quote! { "com.example.Bar$Inner" },
+            quote::quote! { "com/example/Bar$Inner" },
+            quote::quote! { "Lcom/example/Bar$Inner;" },
+        ] {
+            let expanded = jni_fn2(attr, source.clone());
+            assert_eq!(format!("{}", expanded), format!("{}", expected));
+        }
+
+        let expanded = jni_fn2(quote::quote! { "com/example.Bar" }, source.clone());
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid class name supplied to `jni_fn` attribute: `com/example.Bar` mixes the `/`-separated internal form with the `.`-separated binary form; write `com/example/Bar` or `com.example.Bar`" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_cfg_attribute_copied() {
+        let attr = quote::quote! { "com.example.Bar" };
         let source = quote::quote! {
-            pub unsafe fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+            #[cfg(feature = "jni")]
+            #[inline]
+            pub fn close_it(env: JNIEnv, _: JClass) {
                 unimplemented!()
             }
         };
@@ -472,23 +1842,32 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn Java_com_example_Bar_close_1it (env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                    #[cfg(feature = "jni")]
+                    #[inline]
+                    pub fn close_it(env: JNIEnv, _: JClass) {
                         unimplemented!()
                     }
+                    #[cfg(feature = "jni")]
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                    #[cfg(feature = "jni")]
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        close_it(__jni_arg0, __jni_arg1)
+                    }
                 }
             )
         );
     }
 
     #[test]
-    fn test_non_function() {
-        let attr = quote::quote! { "com.example.Foo" };
+    fn test_export_name() {
+        let attr = quote::quote! { "com.example.Bar", export_name };
         let source = quote::quote! {
-            enum NotAFunction {
-                Variant1,
-                Variant2(u8),
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
             }
         };
 
@@ -499,17 +1878,28 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    ::core::compile_error! { "The `jni_fn` attribute can only be applied to `fn` items" }
+                    pub fn close_it(env: JNIEnv, _: JClass) {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                    #[unsafe(export_name = "Java_com_example_Bar_close_1it")]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn __export_Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        close_it(__jni_arg0, __jni_arg1)
+                    }
                 }
             )
         );
     }
 
     #[test]
-    fn test_empty_attribute() {
-        let attr = quote::quote! {};
+    fn test_signature_const() {
+        let attr = quote::quote! { "com.example.Bar" };
         let source = quote::quote! {
-            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+            #[cfg(feature = "jni")]
+            pub fn r#move(env: JNIEnv, _: JClass, #[jtype("java.util.List")] items: JObject) -> jint {
                 unimplemented!()
             }
         };
@@ -521,73 +1911,1282 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    ::core::compile_error! { "The `jni_fn` attribute must have a single string literal supplied to specify the namespace" }
+                    #[cfg(feature = "jni")]
+                    pub fn r#move(env: JNIEnv, _: JClass, items: JObject) -> jint {
+                        unimplemented!()
+                    }
+                    #[cfg(feature = "jni")]
+                    #[doc = "The JVM method descriptor of `move`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_move: &str = "(Ljava/util/List;)I";
+                    #[cfg(feature = "jni")]
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_move(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JObject) -> jint {
+                        r#move(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                }
+            )
+        );
+
+        let attr = quote::quote! { "com.example.Bar", signature = "(I)V" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) {}
+        };
+        let expanded = jni_fn2(attr, source.clone());
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Parameter `filename` has type `JString`, but the `signature` declares it as `I`" }
+                    #source
                 }
             )
         );
     }
 
     #[test]
-    fn test_invalid_namespace() {
+    fn test_register() {
+        let attr = quote::quote! {
+            "com.example.Bar", alias = "com.example.Foo", register, signature = "(Ljava/lang/String;)Z"
+        };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        let shim: ItemFn = syn::parse_quote! {
+            #[allow(non_snake_case)]
+            extern "system" fn __jni_native_Java_com_example_Bar_close_1it__Ljava_lang_String_2(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+            }
+        };
+        let alias_shim: ItemFn = syn::parse_quote! {
+            #[allow(non_snake_case)]
+            extern "system" fn __jni_native_Java_com_example_Foo_close_1it__Ljava_lang_String_2(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+            }
+        };
+        let native = |class: &str| registry::NativeMethod {
+            class: class.to_string(),
+            name: "close_it".to_string(),
+            descriptor: "(Ljava/lang/String;)Z".to_string(),
+        };
+        let registration = registry::register_native(
+            &shim,
+            &quote::quote! { __jni_native_Java_com_example_Bar_close_1it__Ljava_lang_String_2 },
+            &native("com/example/Bar"),
+        );
+        let alias_registration = registry::register_native(
+            &alias_shim,
+            &quote::quote! { __jni_native_Java_com_example_Foo_close_1it__Ljava_lang_String_2 },
+            &native("com/example/Foo"),
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                    #shim
+                    #registration
+                    #alias_shim
+                    #alias_registration
+                }
+            )
+        );
+
+        for (attr, source, message) in [
+            (
+                quote::quote! { "com.example.Bar", register, type_bound = "JniType" },
+                quote::quote! { pub fn close_it(env: JNIEnv, _: JClass, handle: Handle) {} },
+                "The Java type of parameter `handle` can't be derived from its type `Handle`; annotate it with `#[jtype(\"...\")]`, or pass the method descriptor as `signature`",
+            ),
+            (
+                quote::quote! { "com.example.Bar", register },
+                quote::quote! { pub fn close_it(env: JNIEnv, _: JClass, file: JObject) {} },
+                "The Java type of parameter `file` can't be derived from its type `JObject`; annotate it with `#[jtype(\"...\")]`, or pass the method descriptor as `signature`",
+            ),
+            (
+                quote::quote! { "com.example.Bar", register, export_name },
+                quote::quote! { pub fn close_it(env: JNIEnv, _: JClass) {} },
+                "`export_name` can't be combined with `register`, since registered functions aren't exported",
+            ),
+        ] {
+            let expanded = jni_fn2(attr, source.clone());
+            assert_eq!(
+                format!("{}", expanded),
+                format!(
+                    "{}",
+                    quote::quote! {
+                        ::core::compile_error! { #message }
+                        #source
+                    }
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_register_impl_container() {
+        let attr = quote::quote! { "com.example.Bar", register };
+        let source = quote::quote! {
+            impl Bindings {
+                #[jni(signature = "()V")]
+                pub fn close_it(env: JNIEnv, _: JClass) {
+                    unimplemented!()
+                }
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        let shim: ItemFn = syn::parse_quote! {
+            #[allow(non_snake_case)]
+            extern "system" fn __jni_native_Java_com_example_Bar_close_1it__(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                Self::close_it(__jni_arg0, __jni_arg1)
+            }
+        };
+        let registration = registry::register_native(
+            &shim,
+            &quote::quote! { <Bindings>::__jni_native_Java_com_example_Bar_close_1it__ },
+            &registry::NativeMethod {
+                class: "com/example/Bar".to_string(),
+                name: "close_it".to_string(),
+                descriptor: "()V".to_string(),
+            },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    impl Bindings {
+                        pub fn close_it(env: JNIEnv, _: JClass) {
+                            unimplemented!()
+                        }
+                        #[doc = "The JVM method descriptor of `close_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                        #shim
+                    }
+                    #registration
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_unsafe_fn() {
+        let attr = quote::quote! {
+            "com.example.Bar"
+        };
+        let source = quote::quote! {
+            pub unsafe fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub unsafe fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub unsafe extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        unsafe { close_it(__jni_arg0, __jni_arg1, __jni_arg2) }
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_package_class_method() {
+        let attr = quote::quote! {
+            package = "com.example", class = "Outer.Inner", method = "closeIt"
+        };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Outer_00024Inner_closeIt(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_rename_all_camel_case() {
+        let attr = quote::quote! { "com.example.Bar", rename_all = "camelCase" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_closeIt(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_camel_case_fn() {
+        let attr = quote::quote! { "com.example.Bar" };
+        let source = quote::quote! {
+            impl Bindings {
+                pub fn closeIt(env: JNIEnv, _: JClass) {}
+                pub fn __open_it(env: JNIEnv, _: JClass) {}
+            }
+        };
+
+        let expanded = jni_fn2(attr.clone(), source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    impl Bindings {
+                        #[allow(non_snake_case)]
+                        pub fn closeIt(env: JNIEnv, _: JClass) {}
+                        #[doc = "The JVM method descriptor of `closeIt`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_closeIt: &str = "()V";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub extern "system" fn Java_com_example_Bar_closeIt(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                            Self::closeIt(__jni_arg0, __jni_arg1)
+                        }
+                        pub fn __open_it(env: JNIEnv, _: JClass) {}
+                        #[doc = "The JVM method descriptor of `__open_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE___open_it: &str = "()V";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub extern "system" fn Java_com_example_Bar__1_1open_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                            Self::__open_it(__jni_arg0, __jni_arg1)
+                        }
+                    }
+                }
+            )
+        );
+
+        let source = quote::quote! {
+            /// Closes it.
+            pub fn closeIt(env: JNIEnv, _: JClass) {}
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert!(format!("{}", expanded).starts_with(
+            &quote::quote! {
+                #[doc = r" Closes it."]
+                #[allow(non_snake_case)]
+                pub fn closeIt(env: JNIEnv, _: JClass) {}
+            }
+            .to_string()
+        ));
+    }
+
+    #[test]
+    fn test_class_in_unnamed_package() {
+        let attr = quote::quote! { class = "HelloWorld" };
+        let source = quote::quote! {
+            pub fn hello(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn hello(env: JNIEnv, _: JClass) {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `hello`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_hello: &str = "()V";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_HelloWorld_hello(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        hello(__jni_arg0, __jni_arg1)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_namespace_and_class() {
+        let attr = quote::quote! { "com.example.Foo", class = "Bar" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "The namespace literal can't be combined with `package` or `class` options" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_mod_container() {
+        let attr = quote::quote! { "com.example.Bar", rename_all = "camelCase" };
+        let source = quote::quote! {
+            mod bindings {
+                use super::*;
+
+                pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                    unimplemented!()
+                }
+
+                #[jni("com.example.Baz", method = "open")]
+                pub fn open_it(env: JNIEnv, _: JClass) {
+                    unimplemented!()
+                }
+
+                fn helper() {}
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    mod bindings {
+                        use super::*;
+
+                        pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                            unimplemented!()
+                        }
+                        #[doc = "The JVM method descriptor of `close_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub extern "system" fn Java_com_example_Bar_closeIt(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                            close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                        }
+
+                        pub fn open_it(env: JNIEnv, _: JClass) {
+                            unimplemented!()
+                        }
+                        #[doc = "The JVM method descriptor of `open_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_open_it: &str = "()V";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub extern "system" fn Java_com_example_Baz_open(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                            open_it(__jni_arg0, __jni_arg1)
+                        }
+
+                        fn helper() {}
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_impl_container() {
+        let attr = quote::quote! { package = "com.example", class = "Bar" };
+        let source = quote::quote! {
+            impl Bindings {
+                pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                    unimplemented!()
+                }
+
+                pub(crate) fn helper(&self) {}
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    impl Bindings {
+                        pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                            unimplemented!()
+                        }
+                        #[doc = "The JVM method descriptor of `close_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                            Self::close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                        }
+
+                        pub(crate) fn helper(&self) {}
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_impl_container_receiver() {
+        let attr = quote::quote! { "com.example.Bar" };
+        let source = quote::quote! {
+            impl Bindings {
+                pub fn close_it(&self, env: JNIEnv, _: JClass) {
+                    unimplemented!()
+                }
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "`jni_fn` exported functions in `impl` blocks can't take `self`" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_container_name_collision() {
+        let attr = quote::quote! { "com.example.Bar", rename_all = "camelCase" };
+        let source = quote::quote! {
+            mod bindings {
+                pub fn close_it(env: JNIEnv, _: JClass) {}
+                pub fn closeIt(env: JNIEnv, _: JClass) {}
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "`closeIt` is exported as `Java_com_example_Bar_closeIt`, which is already used by `close_it`" }
+                    mod bindings {
+                        pub fn close_it(env: JNIEnv, _: JClass) {}
+                        #[doc = "The JVM method descriptor of `close_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub extern "system" fn Java_com_example_Bar_closeIt(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                            close_it(__jni_arg0, __jni_arg1)
+                        }
+                        #[allow(non_snake_case)]
+                        pub fn closeIt(env: JNIEnv, _: JClass) {}
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_impl_rename_all_collision() {
+        let attr = quote::quote! { "com.example.Bar", rename_all = "PascalCase" };
+        let source = quote::quote! {
+            impl Bindings {
+                pub fn close_it(env: JNIEnv, _: JClass) {}
+                #[jni(method = "CloseIt")]
+                pub fn close(env: JNIEnv, _: JClass) {}
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert!(format!("{}", expanded).starts_with(
+            &quote::quote! {
+                ::core::compile_error! { "`close` is exported as `Java_com_example_Bar_CloseIt`, which is already used by `close_it`" }
+            }
+            .to_string()
+        ));
+    }
+
+    #[test]
+    fn test_container_method_option() {
+        let attr = quote::quote! { "com.example.Bar", method = "closeIt" };
+        let source = quote::quote! {
+            mod bindings {}
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "The `method` option can't be applied to a `mod` or `impl` block; move it to one of its functions" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_cfg_namespaces() {
+        let attr = quote::quote! {
+            cfg(debug_assertions) = "com.example.debug.Bar", default = "com.example.Bar"
+        };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn close_it(env: JNIEnv, _: JClass) {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                    #[cfg(debug_assertions)]
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_debug_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        close_it(__jni_arg0, __jni_arg1)
+                    }
+                    #[cfg(not(any(debug_assertions)))]
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        close_it(__jni_arg0, __jni_arg1)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_cfg_namespaces_without_default() {
+        let attr = quote::quote! { cfg(any()) = "com.example.Bar" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {}
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "`cfg` namespaces need a `default = \"...\"` namespace for when none of their predicates hold" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_env_namespace() {
+        std::env::set_var("JAVA_NATIVE_TEST_JNI_CLASS", "com.example.Bar");
+        let attr = quote::quote! { env!("JAVA_NATIVE_TEST_JNI_CLASS") };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    const _: &str = ::core::env!("JAVA_NATIVE_TEST_JNI_CLASS");
+                    pub fn close_it(env: JNIEnv, _: JClass) {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        close_it(__jni_arg0, __jni_arg1)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_env_without_name() {
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {}
+        };
+
+        for attr in [
+            quote::quote! { env!() },
+            quote::quote! { option_env!().unwrap_or("com.example.Bar") },
+        ] {
+            let expanded = jni_fn2(attr, source.clone());
+
+            assert_eq!(
+                format!("{}", expanded),
+                format!(
+                    "{}",
+                    quote::quote! {
+                        ::core::compile_error! { "Expected an environment variable name as a string literal" }
+                        #source
+                    }
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_alias() {
+        let attr = quote::quote! { "com.example.Bar", alias = "com.old.Bar", alias = "Bar" };
+        let source = quote::quote! {
+            pub fn close_it(mut env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn close_it(mut env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `close_it`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_close_it: &str = "(Ljava/lang/String;)Z";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_old_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jboolean {
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_alias_unsafe_in_impl() {
+        let attr = quote::quote! { "com.example.Bar", alias = "com.old.Bar" };
+        let source = quote::quote! {
+            impl Bindings {
+                pub unsafe fn close_it<'local>(env: JNIEnv<'local>, _: JClass<'local>) {
+                    unimplemented!()
+                }
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    impl Bindings {
+                        pub unsafe fn close_it<'local>(env: JNIEnv<'local>, _: JClass<'local>) {
+                            unimplemented!()
+                        }
+                        #[doc = "The JVM method descriptor of `close_it`."]
+                        #[allow(non_upper_case_globals)]
+                        pub const __JNI_SIGNATURE_close_it: &str = "()V";
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub unsafe extern "system" fn Java_com_example_Bar_close_1it<'local>(__jni_arg0: JNIEnv<'local>, __jni_arg1: JClass<'local>) {
+                            unsafe { Self::close_it(__jni_arg0, __jni_arg1) }
+                        }
+                        #[unsafe(no_mangle)]
+                        #[allow(non_snake_case)]
+                        pub unsafe extern "system" fn Java_com_old_Bar_close_1it<'local>(__jni_arg0: JNIEnv<'local>, __jni_arg1: JClass<'local>) {
+                            unsafe { Self::close_it(__jni_arg0, __jni_arg1) }
+                        }
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_alias() {
+        let attr = quote::quote! { "com.example.Bar", alias = "com.old Bar" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {}
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid package namespace supplied as `alias` to `jni_fn` attribute: segment `old Bar` contains ' ' (U+0020), which isn't allowed in names" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_kotlin_file_internal() {
+        let attr = quote::quote! {
+            kotlin_file = "com.example.Utils", kotlin_internal_module = "app_release"
+        };
+        let source = quote::quote! {
+            pub fn compute(env: JNIEnv, _: JClass) -> jint {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn compute(env: JNIEnv, _: JClass) -> jint {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `compute`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_compute: &str = "()I";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_UtilsKt_compute_00024app_1release(__jni_arg0: JNIEnv, __jni_arg1: JClass) -> jint {
+                        compute(__jni_arg0, __jni_arg1)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_kotlin_companion() {
+        let attr = quote::quote! { kotlin_companion = "com.example.Foo", rename_all = "camelCase" };
+        let source = quote::quote! {
+            pub fn create_foo(env: JNIEnv, _: JObject) -> jobject {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn create_foo(env: JNIEnv, _: JObject) -> jobject {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `create_foo`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_create_foo: &str = "()Ljava/lang/Object;";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Foo_00024Companion_createFoo(__jni_arg0: JNIEnv, __jni_arg1: JObject) -> jobject {
+                        create_foo(__jni_arg0, __jni_arg1)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_overloaded_signature() {
+        let attr = quote::quote! {
+            "com.example.Codec", signature = "(Ljava/lang/String;)[B"
+        };
+        let source = quote::quote! {
+            pub fn encode(env: JNIEnv, _: JClass, input: JString) -> jbyteArray {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn encode(env: JNIEnv, _: JClass, input: JString) -> jbyteArray {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `encode`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_encode: &str = "(Ljava/lang/String;)[B";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Codec_encode__Ljava_lang_String_2(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: JString) -> jbyteArray {
+                        encode(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let attr = quote::quote! { "com.example.Codec", signature = "(Ljava/lang/String)V" };
+        let source = quote::quote! {
+            pub fn encode(env: JNIEnv, _: JClass, input: JString) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid method descriptor supplied as `signature`: missing `;` after class name in `Ljava/lang/String`" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_keyword_method_name() {
+        let attr = quote::quote! { "com.example.Foo" };
+        let source = quote::quote! {
+            pub fn native(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid method name derived from the function name (set `method = \"...\"` to override it): `native` is a reserved Java keyword (add `relaxed` to allow it)" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_relaxed_names() {
+        let attr = quote::quote! { "com.example.Is Valid", relaxed, method = "is valid" };
+        let source = quote::quote! {
+            pub fn is_valid(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn is_valid(env: JNIEnv, _: JClass) {
+                        unimplemented!()
+                    }
+                    #[doc = "The JVM method descriptor of `is_valid`."]
+                    #[allow(non_upper_case_globals)]
+                    pub const __JNI_SIGNATURE_is_valid: &str = "()V";
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Is_00020Valid_is_00020valid(__jni_arg0: JNIEnv, __jni_arg1: JClass) {
+                        is_valid(__jni_arg0, __jni_arg1)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_param_type() {
+        let attr = quote::quote! { "com.example.Bar" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: String) -> Vec<u8> {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Parameter `filename` has type `String`, which can't be passed over the JNI; use a `jni` type like `JString` or `JObject`, or a `jni-sys` type like `jstring`, instead" }
+                    ::core::compile_error! { "The return type `Vec<u8>` can't be passed over the JNI; use a `jni` or `jni-sys` type like `JObject` or `jint`, or allow other types with `type_bound`" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_type_bound() {
+        let attr = quote::quote! { "com.example.Bar", type_bound = "crate::JniType" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, handle: Handle) -> jint {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn close_it(env: JNIEnv, _: JClass, handle: Handle) -> jint {
+                        unimplemented!()
+                    }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    pub extern "system" fn Java_com_example_Bar_close_1it(__jni_arg0: JNIEnv, __jni_arg1: JClass, __jni_arg2: Handle) -> jint {
+                        {
+                            fn __jni_type_bound<T: crate::JniType>() {}
+                            __jni_type_bound::<Handle>();
+                        }
+                        close_it(__jni_arg0, __jni_arg1, __jni_arg2)
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_unexportable_fn() {
+        let attr = quote::quote! { "com.example.Bar" };
+        for (source, message) in [
+            (
+                quote::quote! { pub async fn close_it(env: JNIEnv, _: JClass) {} },
+                "`jni_fn` attributed functions can't be `async`, since the JVM calls them synchronously; block on the future inside the function instead",
+            ),
+            (
+                quote::quote! { pub const fn close_it(env: JNIEnv, _: JClass) {} },
+                "`jni_fn` attributed functions can't be `const`",
+            ),
+            (
+                quote::quote! { pub fn close_it<'local, T>(env: JNIEnv<'local>, _: JClass<'local>) {} },
+                "`jni_fn` attributed functions can't be generic over `T`, since only a single instantiation could be exported; only lifetime parameters are allowed",
+            ),
+            (
+                quote::quote! { pub fn close_it<const N: usize>(env: JNIEnv, _: JClass) {} },
+                "`jni_fn` attributed functions can't be generic over `N`, since only a single instantiation could be exported; only lifetime parameters are allowed",
+            ),
+            (
+                quote::quote! { #[unsafe(export_name = "close")] pub fn close_it(env: JNIEnv, _: JClass) {} },
+                "Remove the `export_name` attribute; `jni_fn` attributed functions are exported under the right name automatically",
+            ),
+        ] {
+            let expanded = jni_fn2(attr.clone(), source.clone());
+            assert_eq!(
+                format!("{}", expanded),
+                format!(
+                    "{}",
+                    quote::quote! {
+                        ::core::compile_error! { #message }
+                        #source
+                    }
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        let attr = quote::quote! { "com.example.Foo", sigature = "()V" };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Unknown `jni_fn` attribute option `sigature`" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_non_function() {
+        let attr = quote::quote! { "com.example.Foo" };
+        let source = quote::quote! {
+            enum NotAFunction {
+                Variant1,
+                Variant2(u8),
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "The `jni_fn` attribute can only be applied to `fn` items, inline `mod` blocks and inherent `impl` blocks" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_empty_attribute() {
+        let attr = quote::quote! {};
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "The `jni_fn` attribute must specify a namespace, either as a string literal or with a `class` option" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_namespace() {
         let attr = quote::quote! { "." };
         let source = quote::quote! {
-            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+            pub fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid package namespace supplied to `jni_fn` attribute: `.` contains an empty segment" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_namespace_suggestion() {
+        let attr = quote::quote! { "com.example.Foo " };
+        let source = quote::quote! {
+            pub fn close_it(env: JNIEnv, _: JClass) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_fn2(attr, source.clone());
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid package namespace supplied to `jni_fn` attribute: segment `Foo ` contains ' ' (U+0020), which isn't allowed in names; did you mean `com.example.Foo`?" }
+                    #source
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_specified_abi() {
+        let attr = quote::quote! { "com.example.Foo" };
+        let source = quote::quote! {
+            pub extern "C" fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
                 unimplemented!()
             }
         };
 
-        let expanded = jni_fn2(attr, source);
+        let expanded = jni_fn2(attr, source.clone());
 
         assert_eq!(
             format!("{}", expanded),
             format!(
                 "{}",
                 quote::quote! {
-                    ::core::compile_error! { "Invalid package namespace supplied to `jni_fn` attribute" }
+                    ::core::compile_error! { "Don't specify an ABI for `jni_fn` attributed functions - the correct ABI will be added automatically" }
+                    #source
                 }
             )
         );
     }
 
     #[test]
-    fn test_specified_abi() {
+    fn test_wrong_visibility() {
         let attr = quote::quote! { "com.example.Foo" };
         let source = quote::quote! {
-            pub extern "C" fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+            fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
                 unimplemented!()
             }
         };
 
-        let expanded = jni_fn2(attr, source);
+        let expanded = jni_fn2(attr, source.clone());
 
         assert_eq!(
             format!("{}", expanded),
             format!(
                 "{}",
                 quote::quote! {
-                    ::core::compile_error! { "Don't specify an ABI for `jni_fn` attributed functions - the correct ABI will be added automatically" }
+                    ::core::compile_error! { "`jni_fn` attributed functions must have public visibility (`pub`)" }
+                    #source
                 }
             )
         );
     }
 
     #[test]
-    fn test_wrong_visibility() {
-        let attr = quote::quote! { "com.example.Foo" };
+    fn test_errors_combined() {
+        let attr = quote::quote! { "com.example.1Bar", alias = "com.example.Old Bar" };
         let source = quote::quote! {
-            fn close_it(env: JNIEnv, _: JClass, filename: JString) -> jboolean {
+            extern "C" fn close_it<T>(env: JNIEnv, _: JClass, filename: String) {
                 unimplemented!()
             }
         };
 
-        let expanded = jni_fn2(attr, source);
+        let expanded = jni_fn2(attr, source.clone());
 
         assert_eq!(
             format!("{}", expanded),
             format!(
                 "{}",
                 quote::quote! {
+                    ::core::compile_error! { "Invalid package namespace supplied to `jni_fn` attribute: segment `1Bar` starts with the digit `1`" }
+                    ::core::compile_error! { "Invalid package namespace supplied as `alias` to `jni_fn` attribute: segment `Old Bar` contains ' ' (U+0020), which isn't allowed in names" }
+                    ::core::compile_error! { "Don't specify an ABI for `jni_fn` attributed functions - the correct ABI will be added automatically" }
                     ::core::compile_error! { "`jni_fn` attributed functions must have public visibility (`pub`)" }
+                    ::core::compile_error! { "`jni_fn` attributed functions can't be generic over `T`, since only a single instantiation could be exported; only lifetime parameters are allowed" }
+                    ::core::compile_error! { "Parameter `filename` has type `String`, which can't be passed over the JNI; use a `jni` type like `JString` or `JObject`, or a `jni-sys` type like `jstring`, instead" }
+                    #source
                 }
             )
         );
@@ -704,11 +3303,20 @@ mod tests {
         );
     }
 
+    /// The native method registration and `on_load_init` functions run by an `on_load` entry
+    /// point, given the raw VM pointer.
+    fn on_load_registries(vm: TokenStream) -> TokenStream {
+        let natives = registry::register_natives(&vm);
+        let init = registry::run(registry::Registry::Init, &vm);
+        quote::quote! { #natives #init }
+    }
+
     #[test]
     fn test_hook_fn_mangle_on_load_dynamic() {
+        let init = on_load_registries(quote::quote! { __jni_vm });
         let attr = TokenStream::new();
         let source = quote::quote! {
-            pub unsafe fn on_load(vm: JavaVM, _: ()) -> jint {
+            pub unsafe fn on_load(vm: JavaVM) -> jint {
                 unimplemented!()
             }
         };
@@ -720,11 +3328,31 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn JNI_OnLoad (vm: JavaVM, _: ()) -> jint {
+                    pub unsafe fn on_load(vm: JavaVM) -> jint {
                         unimplemented!()
                     }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub unsafe extern "system" fn JNI_OnLoad(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) -> ::jni::sys::jint {
+                        #init
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ::jni::sys::JNI_ERR,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | unsafe { on_load(__jni_vm) })) {
+                            ::core::result::Result::Ok(version) => version,
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnLoad panicked: {}", message);
+                                ::jni::sys::JNI_ERR
+                            }
+                        }
+                    }
                 }
             )
         );
@@ -732,9 +3360,13 @@ mod tests {
 
     #[test]
     fn test_hook_fn_mangle_on_unload_dynamic() {
+        let cleanup = registry::run(
+            registry::Registry::Cleanup,
+            &quote::quote! { __jni_registry_vm },
+        );
         let attr = TokenStream::new();
         let source = quote::quote! {
-            pub unsafe fn on_unload(vm: JavaVM, _: ()) {
+            pub fn on_unload(vm: &JavaVM) {
                 unimplemented!()
             }
         };
@@ -746,11 +3378,31 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn JNI_OnUnload (vm: JavaVM, _: ()) {
+                    pub fn on_unload(vm: &JavaVM) {
                         unimplemented!()
                     }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub extern "system" fn JNI_OnUnload(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) {
+                        let __jni_registry_vm: *mut ::jni::sys::JavaVM = __jni_vm;
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | on_unload(&__jni_vm))) {
+                            ::core::result::Result::Ok(()) => {},
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnUnload panicked: {}", message);
+                            }
+                        }
+                        #cleanup
+                    }
                 }
             )
         );
@@ -758,9 +3410,10 @@ mod tests {
 
     #[test]
     fn test_hook_fn_mangle_on_load_static_with_quotes() {
+        let init_raw = on_load_registries(quote::quote! { __jni_vm as *mut ::jni::sys::JavaVM });
         let attr = quote::quote! { "example" };
         let source = quote::quote! {
-            pub unsafe fn on_load(vm: JavaVM, _: ()) -> jint {
+            pub unsafe fn on_load(vm: *mut JavaVM, reserved: *mut c_void) -> jint {
                 unimplemented!()
             }
         };
@@ -772,11 +3425,27 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn JNI_OnLoad_example (vm: JavaVM, _: ()) -> jint {
+                    pub unsafe fn on_load(vm: *mut JavaVM, reserved: *mut c_void) -> jint {
                         unimplemented!()
                     }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub unsafe extern "system" fn JNI_OnLoad_example(__jni_vm: *mut JavaVM, __jni_reserved: *mut c_void) -> ::jni::sys::jint {
+                        #init_raw
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | unsafe { on_load(__jni_vm, __jni_reserved) })) {
+                            ::core::result::Result::Ok(version) => version,
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnLoad_example panicked: {}", message);
+                                ::jni::sys::JNI_ERR
+                            }
+                        }
+                    }
                 }
             )
         );
@@ -784,9 +3453,13 @@ mod tests {
 
     #[test]
     fn test_hook_fn_mangle_on_unload_static_with_quotes() {
+        let cleanup = registry::run(
+            registry::Registry::Cleanup,
+            &quote::quote! { __jni_registry_vm },
+        );
         let attr = quote::quote! { "example" };
         let source = quote::quote! {
-            pub unsafe fn on_unload(vm: JavaVM, _: ()) {
+            pub unsafe fn on_unload(vm: JavaVM) {
                 unimplemented!()
             }
         };
@@ -798,11 +3471,31 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn JNI_OnUnload_example (vm: JavaVM, _: ()) {
+                    pub unsafe fn on_unload(vm: JavaVM) {
                         unimplemented!()
                     }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub unsafe extern "system" fn JNI_OnUnload_example(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) {
+                        let __jni_registry_vm: *mut ::jni::sys::JavaVM = __jni_vm;
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | unsafe { on_unload(__jni_vm) })) {
+                            ::core::result::Result::Ok(()) => {},
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnUnload_example panicked: {}", message);
+                            }
+                        }
+                        #cleanup
+                    }
                 }
             )
         );
@@ -810,9 +3503,10 @@ mod tests {
 
     #[test]
     fn test_hook_fn_mangle_on_load_static_no_quotes() {
+        let init = on_load_registries(quote::quote! { __jni_vm });
         let attr = quote::quote! { example };
         let source = quote::quote! {
-            pub unsafe fn on_load(vm: JavaVM, _: ()) -> jint {
+            pub fn on_load(vm: &JavaVM) -> jint {
                 unimplemented!()
             }
         };
@@ -824,11 +3518,31 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
-                    #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn JNI_OnLoad_example (vm: JavaVM, _: ()) -> jint {
+                    pub fn on_load(vm: &JavaVM) -> jint {
                         unimplemented!()
                     }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub extern "system" fn JNI_OnLoad_example(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) -> ::jni::sys::jint {
+                        #init
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ::jni::sys::JNI_ERR,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | on_load(&__jni_vm))) {
+                            ::core::result::Result::Ok(version) => version,
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnLoad_example panicked: {}", message);
+                                ::jni::sys::JNI_ERR
+                            }
+                        }
+                    }
                 }
             )
         );
@@ -836,9 +3550,13 @@ mod tests {
 
     #[test]
     fn test_hook_fn_mangle_on_unload_static_no_quotes() {
+        let cleanup = registry::run(
+            registry::Registry::Cleanup,
+            &quote::quote! { __jni_registry_vm },
+        );
         let attr = quote::quote! { example };
         let source = quote::quote! {
-            pub unsafe fn on_unload(vm: JavaVM, _: ()) {
+            pub fn on_unload(vm: JavaVM) {
                 unimplemented!()
             }
         };
@@ -850,13 +3568,569 @@ mod tests {
             format!(
                 "{}",
                 quote::quote! {
-                    #[no_mangle]
+                    pub fn on_unload(vm: JavaVM) {
+                        unimplemented!()
+                    }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub extern "system" fn JNI_OnUnload_example(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) {
+                        let __jni_registry_vm: *mut ::jni::sys::JavaVM = __jni_vm;
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | on_unload(__jni_vm))) {
+                            ::core::result::Result::Ok(()) => {},
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnUnload_example panicked: {}", message);
+                            }
+                        }
+                        #cleanup
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_hook_invalid_libname() {
+        let source = quote::quote! {
+            pub fn on_load(vm: JavaVM) -> jint {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_hook(
+            JniExportType::OnLoad,
+            source.clone(),
+            quote::quote! { "libexample.so" },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid library name `libexample.so` supplied to `on_load`: '.' can't appear in a symbol name; did you mean `example`?" }
+                }
+            )
+        );
+
+        let expanded = jni_hook(
+            JniExportType::OnLoad,
+            source.clone(),
+            quote::quote! { "my-lib" },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "Invalid library name `my-lib` supplied to `on_load`: '-' can't appear in a symbol name; did you mean `my_lib`?" }
+                }
+            )
+        );
+
+        let expanded = jni_hook(
+            JniExportType::OnLoad,
+            source,
+            quote::quote! { example, other },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "The `on_load` attribute takes at most one library name" }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_libname() {
+        let span = proc_macro2::Span::call_site();
+        let names = ["my_lib".to_string(), "my_lib_rs".to_string()];
+        assert!(check_libname("my_lib", span, "on_load", &names).is_ok());
+        assert!(check_libname("my_lib_rs", span, "on_load", &names).is_ok());
+        assert!(check_libname("anything", span, "on_load", &[]).is_ok());
+        assert_eq!(
+            check_libname("my_lbi", span, "on_unload", &names)
+                .unwrap_err()
+                .to_string(),
+            "The library name `my_lbi` supplied to `on_unload` isn't the name of this crate's library, `my_lib`, so the JVM won't call the hook; add `unchecked_libname` if the library is linked under another name"
+        );
+
+        let args = parse_hook_args(
+            quote::quote! { example, unchecked_libname, version = "1.8" },
+            "on_load",
+        )
+        .unwrap();
+        assert_eq!(args.libname, "example");
+        assert!(args.unchecked_libname);
+        assert_eq!(
+            parse_hook_args(
+                quote::quote! { example, unchecked_libname, unchecked_libname },
+                "on_load",
+            )
+            .err()
+            .map(|e| e.to_string()),
+            Some("`unchecked_libname` may only be specified once".to_string())
+        );
+    }
+
+    #[test]
+    fn test_suggest_hook_libname() {
+        assert_eq!(
+            suggest_hook_libname("libexample.dylib").as_deref(),
+            Some("example")
+        );
+        assert_eq!(
+            suggest_hook_libname("example.dll").as_deref(),
+            Some("example")
+        );
+        assert_eq!(
+            suggest_hook_libname("my.lib-name").as_deref(),
+            Some("my_lib_name")
+        );
+        assert_eq!(suggest_hook_libname("1example"), None);
+        assert_eq!(suggest_hook_libname("example"), None);
+    }
+
+    #[test]
+    fn test_hook_version_negotiation() {
+        let init = on_load_registries(quote::quote! { __jni_vm });
+        let attr = quote::quote! { version = "9", min = "1.6" };
+        let source = quote::quote! {
+            pub fn on_load(vm: &JavaVM) {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_hook(JniExportType::OnLoad, source, attr);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn on_load(vm: &JavaVM) {
+                        unimplemented!()
+                    }
+                    #[unsafe(no_mangle)]
                     #[allow(non_snake_case)]
-                    pub unsafe extern "system" fn JNI_OnUnload_example (vm: JavaVM, _: ()) {
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub extern "system" fn JNI_OnLoad(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) -> ::jni::sys::jint {
+                        let __jni_version = {
+                            let vm: *mut ::jni::sys::JavaVM = __jni_vm;
+                            let get_env = match vm.is_null() {
+                                true => ::core::option::Option::None,
+                                false => unsafe { (**vm).GetEnv },
+                            };
+                            [589824i32, 65544i32, 65542i32].into_iter().find(|&version| {
+                                let mut env = ::core::ptr::null_mut();
+                                get_env.is_some_and(|get_env| {
+                                    unsafe { get_env(vm, &mut env, version) == ::jni::sys::JNI_OK }
+                                })
+                            })
+                        };
+                        let ::core::option::Option::Some(__jni_version) = __jni_version else {
+                            return ::jni::sys::JNI_ERR;
+                        };
+                        #init
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ::jni::sys::JNI_ERR,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | on_load(&__jni_vm))) {
+                            ::core::result::Result::Ok(()) => __jni_version,
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnLoad panicked: {}", message);
+                                ::jni::sys::JNI_ERR
+                            }
+                        }
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_hook_result() {
+        let init = on_load_registries(quote::quote! { __jni_vm });
+        let attr = TokenStream::new();
+        let source = quote::quote! {
+            pub fn on_load(vm: JavaVM) -> Result<jint, Error> {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_hook(JniExportType::OnLoad, source, attr);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    pub fn on_load(vm: JavaVM) -> Result<jint, Error> {
                         unimplemented!()
                     }
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub extern "system" fn JNI_OnLoad(__jni_vm: *mut ::jni::sys::JavaVM, _: *mut ::core::ffi::c_void) -> ::jni::sys::jint {
+                        #init
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ::jni::sys::JNI_ERR,
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | on_load(__jni_vm))) {
+                            ::core::result::Result::Ok(::core::result::Result::Ok(version)) => version,
+                            ::core::result::Result::Ok(::core::result::Result::Err(error)) => {
+                                ::std::eprintln!("JNI_OnLoad failed: {:?}", error);
+                                ::jni::sys::JNI_ERR
+                            }
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("JNI_OnLoad panicked: {}", message);
+                                ::jni::sys::JNI_ERR
+                            }
+                        }
+                    }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_hook_version_errors() {
+        for (export, attr, source, message) in [
+            (
+                JniExportType::OnLoad,
+                quote::quote! { example, version = "1.8", min = "1.6" },
+                quote::quote! { pub fn on_load(vm: JavaVM) {} },
+                "`JNI_OnLoad_example` is called for a statically linked library, which must support at least JNI 1.8; raise the `min` to \"1.8\"",
+            ),
+            (
+                JniExportType::OnLoad,
+                quote::quote! { example },
+                quote::quote! { pub fn on_load(vm: JavaVM) -> jint { JNI_VERSION_1_6 } },
+                "`JNI_OnLoad_example` is called for a statically linked library, which must return at least `JNI_VERSION_1_8`",
+            ),
+            (
+                JniExportType::OnLoad,
+                quote::quote! { version = "1.8" },
+                quote::quote! { pub fn on_load(vm: JavaVM) -> jint { JNI_VERSION_1_8 } },
+                "`on_load` hooks with a `version` can only return `()` or `Result<(), E>`, since the negotiated version is returned for them",
+            ),
+            (
+                JniExportType::OnUnload,
+                quote::quote! { version = "1.8" },
+                quote::quote! { pub fn on_unload(vm: JavaVM) {} },
+                "JNI versions are only negotiated by `on_load` hooks",
+            ),
+            (
+                JniExportType::OnLoad,
+                quote::quote! { example, versions = "1.8" },
+                quote::quote! { pub fn on_load(vm: JavaVM) {} },
+                "Unknown `on_load` attribute option `versions`",
+            ),
+        ] {
+            let expanded = jni_hook(export, source, attr);
+            assert_eq!(
+                format!("{}", expanded),
+                format!(
+                    "{}",
+                    quote::quote! {
+                        ::core::compile_error! { #message }
+                    }
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_hook_auto() {
+        let source = quote::quote! {
+            pub fn on_unload(vm: JavaVM) {}
+        };
+        let function: ItemFn = syn::parse2(source.clone()).unwrap();
+        let entry_points = ["JNI_OnUnload", "JNI_OnUnload_java_native"].map(|symbol| {
+            let signature = hook::HookSignature {
+                vm: hook::VmParam::Owned,
+                options: false,
+                output: hook::HookOutput::Unit,
+            };
+            hook::create_entry_point(&function, symbol, signature, JniExportType::OnUnload, None)
+        });
+        let [dynamic, static_] = &entry_points;
+
+        let expanded = jni_hook(
+            JniExportType::OnUnload,
+            source.clone(),
+            quote::quote! { auto },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!("{}", quote::quote! { #source #dynamic #static_ })
+        );
+
+        let expanded = jni_hook(
+            JniExportType::OnUnload,
+            source.clone(),
+            quote::quote! { auto(feature = "static") },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    #source
+                    #[cfg(not(feature = "static"))]
+                    #dynamic
+                    #[cfg(feature = "static")]
+                    #static_
+                }
+            )
+        );
+
+        // a library which is actually called `auto`
+        let expanded = jni_hook(
+            JniExportType::OnUnload,
+            source.clone(),
+            quote::quote! { r#auto },
+        )
+        .to_string();
+        assert!(expanded.contains("fn JNI_OnUnload_auto ("));
+        assert!(!expanded.contains("fn JNI_OnUnload ("));
+
+        let expanded = jni_hook(
+            JniExportType::OnLoad,
+            quote::quote! { pub fn on_load(vm: JavaVM) {} },
+            quote::quote! { auto, version = "1.6" },
+        );
+        let message = "`JNI_OnLoad_java_native` is called for a statically linked library, which must support at least JNI 1.8; raise the `version` to \"1.8\"";
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { #message }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_agent_fn_mangle() {
+        let attr = quote::quote! { profiler, unchecked_libname };
+        let source = quote::quote! {
+            pub fn agent_on_load(vm: JavaVM, options: &str) -> Result<(), String> {
+                unimplemented!()
+            }
+        };
+
+        let expanded = jni_hook(JniExportType::AgentOnLoad, source.clone(), attr);
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    #source
+                    #[unsafe(no_mangle)]
+                    #[allow(non_snake_case)]
+                    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                    pub extern "system" fn Agent_OnLoad_profiler(__jni_vm: *mut ::jni::sys::JavaVM, __jni_options: *mut ::core::ffi::c_char, _: *mut ::core::ffi::c_void) -> ::jni::sys::jint {
+                        let __jni_vm = match unsafe { ::jni::JavaVM::from_raw(__jni_vm) } {
+                            ::core::result::Result::Ok(vm) => vm,
+                            ::core::result::Result::Err(_) => return ::jni::sys::JNI_ERR,
+                        };
+                        let __jni_options = match __jni_options.is_null() {
+                            true => "",
+                            false => match unsafe { ::core::ffi::CStr::from_ptr(__jni_options) }.to_str() {
+                                ::core::result::Result::Ok(options) => options,
+                                ::core::result::Result::Err(error) => {
+                                    ::std::eprintln!("Agent_OnLoad_profiler options aren't valid UTF-8: {}", error);
+                                    return ::jni::sys::JNI_ERR;
+                                }
+                            },
+                        };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(| | agent_on_load(__jni_vm, __jni_options))) {
+                            ::core::result::Result::Ok(::core::result::Result::Ok(())) => ::jni::sys::JNI_OK,
+                            ::core::result::Result::Ok(::core::result::Result::Err(error)) => {
+                                ::std::eprintln!("Agent_OnLoad_profiler failed: {:?}", error);
+                                ::jni::sys::JNI_ERR
+                            }
+                            ::core::result::Result::Err(payload) => {
+                                let message = payload
+                                    .downcast_ref::<&str>()
+                                    .copied()
+                                    .or_else(| | payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                    .unwrap_or("Box<dyn Any>");
+                                ::std::eprintln!("Agent_OnLoad_profiler panicked: {}", message);
+                                ::jni::sys::JNI_ERR
+                            }
+                        }
+                    }
+                }
+            )
+        );
+
+        let source = quote::quote! {
+            pub unsafe fn agent_on_unload(vm: *mut JavaVM) {}
+        };
+        let expanded = jni_hook(JniExportType::AgentOnUnload, source, TokenStream::new());
+        assert!(expanded.to_string().contains(
+            &quote::quote! {
+                pub unsafe extern "system" fn Agent_OnUnload(__jni_vm: *mut JavaVM)
+            }
+            .to_string()
+        ));
+
+        let expanded = jni_hook(
+            JniExportType::AgentOnAttach,
+            quote::quote! { pub fn agent_on_attach(vm: JavaVM) {} },
+            quote::quote! { version = "1.8" },
+        );
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    ::core::compile_error! { "JNI versions are only negotiated by `on_load` hooks" }
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_registered_fn() {
+        let source = quote::quote! {
+            fn cache_classes(vm: &JavaVM) -> jni::errors::Result<()> {
+                unimplemented!()
+            }
+        };
+        let function: ItemFn = syn::parse2(source.clone()).unwrap();
+        let signature = hook::HookSignature {
+            vm: hook::VmParam::Borrowed,
+            options: false,
+            output: hook::HookOutput::UnitResult,
+        };
+        let registration = registry::register(&function, registry::Registry::Init, -10, signature);
+
+        let expanded = jni_registered(
+            registry::Registry::Init,
+            source.clone(),
+            quote::quote! { priority = -10 },
+        );
+
+        assert_eq!(
+            format!("{}", expanded),
+            format!("{}", quote::quote! { #source #registration })
+        );
+    }
+
+    #[test]
+    fn test_registered_fn_errors() {
+        for (registry, attr, source, message) in [
+            (
+                registry::Registry::Init,
+                quote::quote! {},
+                quote::quote! { struct Classes; },
+                "The `on_load_init` attribute can only be applied to `fn` items",
+            ),
+            (
+                registry::Registry::Init,
+                quote::quote! { priority = 1, order = 2 },
+                quote::quote! { fn cache_classes(vm: &JavaVM) {} },
+                "Unknown `on_load_init` attribute option `order`; expected `priority`",
+            ),
+            (
+                registry::Registry::Init,
+                quote::quote! {},
+                quote::quote! { fn cache_classes(vm: &JavaVM) -> jint { JNI_VERSION_1_8 } },
+                "`on_load_init` hooks can only return `()` or `Result<(), E>`",
+            ),
+            (
+                registry::Registry::Cleanup,
+                quote::quote! {},
+                quote::quote! { fn flush_logs() {} },
+                "`on_unload_cleanup` hooks must take the VM as `vm: jni::JavaVM` or `vm: &jni::JavaVM`, or the raw `vm: *mut jni::sys::JavaVM, reserved: *mut c_void` pointers",
+            ),
+        ] {
+            let expanded = jni_registered(registry, source, attr);
+            assert_eq!(
+                format!("{}", expanded),
+                format!(
+                    "{}",
+                    quote::quote! {
+                        ::core::compile_error! { #message }
+                    }
+                )
+            );
+        }
+
+        let expanded = jni_registered(
+            registry::Registry::Cleanup,
+            quote::quote! { async fn flush_logs<T>(vm: &JavaVM) {} },
+            TokenStream::new(),
+        );
+        let expected = [
+            "`on_unload_cleanup` functions can't be `async`, since the JVM calls them synchronously; block on the future inside the function instead",
+            "`on_unload_cleanup` functions can't be generic over `T`, since only a single instantiation could be exported; only lifetime parameters are allowed",
+        ];
+        assert_eq!(
+            format!("{}", expanded),
+            format!(
+                "{}",
+                quote::quote! {
+                    #(::core::compile_error! { #expected })*
                 }
             )
         );
     }
+
+    #[test]
+    fn test_unexportable_hook() {
+        for (source, message) in [
+            (
+                quote::quote! { pub async fn on_load(vm: JavaVM) -> jint { unimplemented!() } },
+                "JNI hook functions can't be `async`, since the JVM calls them synchronously; block on the future inside the function instead",
+            ),
+            (
+                quote::quote! { pub fn on_load<T>(vm: JavaVM) -> jint { unimplemented!() } },
+                "JNI hook functions can't be generic over `T`, since only a single instantiation could be exported; only lifetime parameters are allowed",
+            ),
+            (
+                quote::quote! { #[no_mangle] pub fn on_load(vm: JavaVM) -> jint { unimplemented!() } },
+                "Remove the `no_mangle` attribute; JNI hook functions are exported under the right name automatically",
+            ),
+        ] {
+            let expanded = jni_hook(JniExportType::OnLoad, source, TokenStream::new());
+            assert_eq!(
+                format!("{}", expanded),
+                format!(
+                    "{}",
+                    quote::quote! {
+                        ::core::compile_error! { #message }
+                    }
+                )
+            );
+        }
+    }
 }
@@ -0,0 +1,588 @@
+//! Functions registered with `on_load_init` and `on_unload_cleanup`, which are collected at link
+//! time and run by the entry points of `on_load` and `on_unload` hooks, wherever those are defined.
+//! Native methods exported with `#[jni(register)]` are collected the same way, and registered with
+//! `RegisterNatives` by the entry points of `on_load` hooks.
+//!
+//! Each registration is a `#[used]` static in a dedicated link section, which the linker gathers
+//! from every crate linked into the library. The entry points then read the section between its
+//! bounds: the `__start_`/`__stop_` symbols on ELF targets, the `section$start`/`section$end`
+//! symbols on Apple targets, and markers in the `$a` and `$c` subsections on Windows, which the
+//! linker sorts around the `$b` entries. Nothing is collected on other targets.
+//!
+//! The entries are `(priority, name, function)` tuples, where the function takes the raw VM
+//! pointer and returns whether it succeeded. Native methods are `(class, name, descriptor,
+//! function)` tuples, where the strings are NUL-terminated and the function returns the pointer to
+//! the method's `extern "system"` function.
+
+use proc_macro2::TokenStream;
+use syn::ItemFn;
+
+use crate::hook::{self, HookSignature, VmParam};
+
+/// A link-time collection of registered functions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Registry {
+    /// `on_load_init` functions, which run before `on_load` hooks and can fail loading.
+    Init,
+    /// `on_unload_cleanup` functions, which run after `on_unload` hooks.
+    Cleanup,
+    /// Native methods exported with `#[jni(register)]`, which are registered before `on_load`
+    /// hooks and `on_load_init` functions run.
+    Natives,
+}
+
+impl Registry {
+    pub(crate) fn attribute(self) -> &'static str {
+        match self {
+            Registry::Init => "on_load_init",
+            Registry::Cleanup => "on_unload_cleanup",
+            Registry::Natives => "jni",
+        }
+    }
+
+    /// The name of the section on ELF targets, which must be a C identifier for the linker to
+    /// define its bounds.
+    fn elf_section(self) -> &'static str {
+        match self {
+            Registry::Init => "java_native_on_load_init",
+            Registry::Cleanup => "java_native_on_unload_cleanup",
+            Registry::Natives => "java_native_natives",
+        }
+    }
+
+    /// The name of the section in the `__DATA` segment on Apple targets, which is limited to 16
+    /// characters.
+    fn apple_section(self) -> &'static str {
+        match self {
+            Registry::Init => "__jni_init",
+            Registry::Cleanup => "__jni_cleanup",
+            Registry::Natives => "__jni_natives",
+        }
+    }
+
+    /// The name of the section on Windows, without the subsection suffix.
+    fn windows_section(self) -> &'static str {
+        match self {
+            Registry::Init => ".jni_init",
+            Registry::Cleanup => ".jni_cleanup",
+            Registry::Natives => ".jni_natives",
+        }
+    }
+
+    /// The attributes which place a static in the section of this registry, or in a subsection of
+    /// it on Windows.
+    fn section_attrs(self, subsection: &str) -> TokenStream {
+        let elf = self.elf_section();
+        let apple = format!("__DATA,{},regular,no_dead_strip", self.apple_section());
+        let windows = format!("{}${}", self.windows_section(), subsection);
+        let elf_targets = elf_targets();
+        quote::quote! {
+            #[cfg_attr(#elf_targets, unsafe(link_section = #elf))]
+            #[cfg_attr(target_vendor = "apple", unsafe(link_section = #apple))]
+            #[cfg_attr(windows, unsafe(link_section = #windows))]
+        }
+    }
+
+    /// An expression for the entries collected in the section of this registry, as a
+    /// `&'static [Entry]` slice, where `Entry` must name the type of the entries.
+    fn entries(self) -> TokenStream {
+        let elf_start = format!("__start_{}", self.elf_section());
+        let elf_stop = format!("__stop_{}", self.elf_section());
+        let apple_start = format!("\u{1}section$start$__DATA${}", self.apple_section());
+        let apple_end = format!("\u{1}section$end$__DATA${}", self.apple_section());
+        let elf_targets = elf_targets();
+        let section_attrs = self.section_attrs("b");
+        let start_attrs = self.section_attrs("a");
+        let stop_attrs = self.section_attrs("c");
+        quote::quote! {
+            {
+                // the section is defined even if nothing is registered in it
+                #[cfg(any(#elf_targets, target_vendor = "apple"))]
+                #[used]
+                #section_attrs
+                static EMPTY: [Entry; 0] = [];
+                #[cfg(#elf_targets)]
+                let bounds = {
+                    unsafe extern "Rust" {
+                        #[link_name = #elf_start]
+                        static START: Entry;
+                        #[link_name = #elf_stop]
+                        static STOP: Entry;
+                    }
+                    (::core::ptr::addr_of!(START), ::core::ptr::addr_of!(STOP))
+                };
+                #[cfg(target_vendor = "apple")]
+                let bounds = {
+                    unsafe extern "Rust" {
+                        #[link_name = #apple_start]
+                        static START: Entry;
+                        #[link_name = #apple_end]
+                        static STOP: Entry;
+                    }
+                    (::core::ptr::addr_of!(START), ::core::ptr::addr_of!(STOP))
+                };
+                #[cfg(windows)]
+                let bounds = {
+                    #[used]
+                    #start_attrs
+                    static START: [Entry; 0] = [];
+                    #[used]
+                    #stop_attrs
+                    static STOP: [Entry; 0] = [];
+                    (START.as_ptr(), STOP.as_ptr())
+                };
+                #[cfg(not(any(#elf_targets, target_vendor = "apple", windows)))]
+                let bounds: (*const Entry, *const Entry) = (
+                    ::core::ptr::NonNull::dangling().as_ptr(),
+                    ::core::ptr::NonNull::dangling().as_ptr(),
+                );
+
+                let (start, stop) = bounds;
+                let len = (stop as usize - start as usize) / ::core::mem::size_of::<Entry>();
+                unsafe { ::core::slice::from_raw_parts(start, len) }
+            }
+        }
+    }
+}
+
+/// The targets whose sections are bounded by `__start_` and `__stop_` symbols, as a `cfg`
+/// predicate.
+fn elf_targets() -> TokenStream {
+    quote::quote! {
+        any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "illumos"
+        )
+    }
+}
+
+/// Parses the arguments of a registering attribute, which only takes an optional
+/// `priority = N`. Functions with lower priorities run first, and the default is 0.
+pub(crate) fn parse_priority(attr: TokenStream, registry: Registry) -> syn::Result<i32> {
+    use syn::parse::{ParseStream, Parser};
+
+    let parser = |input: ParseStream| {
+        let mut priority = None;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            if key != "priority" {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!(
+                        "Unknown `{}` attribute option `{}`; expected `priority`",
+                        registry.attribute(),
+                        key
+                    ),
+                ));
+            }
+            input.parse::<syn::Token![=]>()?;
+            let minus: Option<syn::Token![-]> = input.parse()?;
+            let literal: syn::LitInt = input.parse()?;
+            let value = match minus {
+                Some(_) => format!("-{}", literal.base10_digits()),
+                None => literal.base10_digits().to_string(),
+            };
+            let value = value.parse::<i32>().map_err(|_| {
+                syn::Error::new(literal.span(), "The `priority` must fit in an `i32`")
+            })?;
+            if priority.replace(value).is_some() {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "`priority` may only be specified once",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+        Ok(priority.unwrap_or(0))
+    };
+    parser.parse2(attr)
+}
+
+/// Registers `target` in `registry` with the given `priority`, through a wrapper which converts
+/// the VM for it and reports whether it succeeded. The wrapper and its registration are defined in
+/// an anonymous constant next to the function.
+pub(crate) fn register(
+    target: &ItemFn,
+    registry: Registry,
+    priority: i32,
+    signature: HookSignature,
+) -> TokenStream {
+    let HookSignature { vm, output, .. } = signature;
+    let failure = quote::quote! { false };
+    let (args, convert) = match vm {
+        VmParam::Raw => (
+            vec![
+                quote::quote! { __jni_vm as _ },
+                quote::quote! { ::core::ptr::null_mut() },
+            ],
+            None,
+        ),
+        _ => (
+            vec![hook::vm_arg(vm, &target.sig)],
+            Some(hook::vm_conversion(&failure)),
+        ),
+    };
+    let target_name = &target.sig.ident;
+    let call = match target.sig.unsafety {
+        Some(_) => quote::quote! { unsafe { #target_name(#(#args),*) } },
+        None => quote::quote! { #target_name(#(#args),*) },
+    };
+    let call = hook::guarded_call(
+        &call,
+        output,
+        &quote::quote! { true },
+        &failure,
+        &format!("`{}`", target_name),
+        target_name.span(),
+    );
+
+    let attrs = target
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"));
+    let section_attrs = registry.section_attrs("b");
+    let name = target_name.to_string();
+    quote::quote! {
+        #(#attrs)*
+        const _: () = {
+            fn __jni_registered(__jni_vm: *mut ::jni::sys::JavaVM) -> bool {
+                #convert
+                #call
+            }
+
+            #[used]
+            #section_attrs
+            static __JNI_REGISTERED: (i32, &str, fn(*mut ::jni::sys::JavaVM) -> bool) = (
+                #priority,
+                ::core::concat!(::core::module_path!(), "::", #name),
+                __jni_registered,
+            );
+        };
+    }
+}
+
+/// A Java method which is registered with `RegisterNatives` rather than exported.
+pub(crate) struct NativeMethod {
+    /// The class declaring the method, in the JVM's internal form (`com/example/Foo`).
+    pub(crate) class: String,
+    pub(crate) name: String,
+    pub(crate) descriptor: String,
+}
+
+/// Registers the `extern "system"` function at `path` as the implementation of `method`. The
+/// registration is defined in an anonymous constant, which is gated by the `cfg` attributes of
+/// `function`, the function that `path` refers to.
+pub(crate) fn register_native(
+    function: &ItemFn,
+    path: &TokenStream,
+    method: &NativeMethod,
+) -> TokenStream {
+    let attrs = function
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"));
+    let section_attrs = Registry::Natives.section_attrs("b");
+    let [class, name, descriptor] =
+        [&method.class, &method.name, &method.descriptor].map(|value| format!("{}\0", value));
+    quote::quote! {
+        #(#attrs)*
+        const _: () = {
+            fn __jni_native() -> *mut ::core::ffi::c_void {
+                #path as *mut ::core::ffi::c_void
+            }
+
+            #[used]
+            #section_attrs
+            static __JNI_NATIVE: (&str, &str, &str, fn() -> *mut ::core::ffi::c_void) =
+                (#class, #name, #descriptor, __jni_native);
+        };
+    }
+}
+
+/// Runs the functions in `registry` with the raw VM pointer `vm`, ordered by their priority and
+/// then by their path. The first `on_load_init` function which fails makes the entry point return
+/// `JNI_ERR`, while every `on_unload_cleanup` function runs regardless.
+pub(crate) fn run(registry: Registry, vm: &TokenStream) -> TokenStream {
+    let entries = registry.entries();
+    let call = match registry {
+        Registry::Init => quote::quote! {
+            if !function(vm) {
+                return ::jni::sys::JNI_ERR;
+            }
+        },
+        Registry::Cleanup => quote::quote! {
+            function(vm);
+        },
+        Registry::Natives => unreachable!("native methods are registered by `register_natives`"),
+    };
+    quote::quote! {
+        {
+            type Entry = (i32, &'static str, fn(*mut ::jni::sys::JavaVM) -> bool);
+            let mut entries = #entries.to_vec();
+            entries.sort_by_key(|&(priority, name, _)| (priority, name));
+            let vm: *mut ::jni::sys::JavaVM = #vm;
+            for (_, _, function) in entries {
+                #call
+            }
+        }
+    }
+}
+
+/// Registers the native methods collected from `#[jni(register)]` functions with the raw VM
+/// pointer `vm`, calling `RegisterNatives` once per class. The classes are looked up with
+/// `FindClass`, which uses the class loader of the class loading the library, and the entry point
+/// returns `JNI_ERR` if any of them can't be found or registered; the exception thrown by the JVM
+/// is left pending, so that it is thrown by `System.loadLibrary`.
+pub(crate) fn register_natives(vm: &TokenStream) -> TokenStream {
+    let entries = Registry::Natives.entries();
+    quote::quote! {
+        {
+            type Entry = (&'static str, &'static str, &'static str, fn() -> *mut ::core::ffi::c_void);
+            let mut entries = #entries.to_vec();
+            entries.sort_by_key(|&(class, name, descriptor, _)| (class, name, descriptor));
+            let vm: *mut ::jni::sys::JavaVM = #vm;
+            if !entries.is_empty() {
+                let mut env = ::core::ptr::null_mut();
+                let get_env = match vm.is_null() {
+                    true => ::core::option::Option::None,
+                    false => unsafe { (**vm).GetEnv },
+                };
+                let attached = get_env.is_some_and(|get_env| unsafe {
+                    get_env(vm, &mut env, ::jni::sys::JNI_VERSION_1_6) == ::jni::sys::JNI_OK
+                });
+                if !attached {
+                    ::std::eprintln!("Failed to get a `JNIEnv` to register native methods");
+                    return ::jni::sys::JNI_ERR;
+                }
+                let env = env as *mut ::jni::sys::JNIEnv;
+                let functions = unsafe { &**env };
+                let (
+                    ::core::option::Option::Some(find_class),
+                    ::core::option::Option::Some(register_natives),
+                    ::core::option::Option::Some(delete_local_ref),
+                ) = (functions.FindClass, functions.RegisterNatives, functions.DeleteLocalRef)
+                else {
+                    return ::jni::sys::JNI_ERR;
+                };
+                for methods in entries.chunk_by(|a, b| a.0 == b.0) {
+                    let class_name = methods[0].0;
+                    let class = unsafe { find_class(env, class_name.as_ptr().cast()) };
+                    if class.is_null() {
+                        ::std::eprintln!(
+                            "Failed to find class `{}` to register its native methods",
+                            class_name.trim_end_matches('\0')
+                        );
+                        return ::jni::sys::JNI_ERR;
+                    }
+                    let natives: ::std::vec::Vec<::jni::sys::JNINativeMethod> = methods
+                        .iter()
+                        .map(|&(_, name, descriptor, function)| ::jni::sys::JNINativeMethod {
+                            name: name.as_ptr() as *mut _,
+                            signature: descriptor.as_ptr() as *mut _,
+                            fnPtr: function(),
+                        })
+                        .collect();
+                    let status = unsafe {
+                        register_natives(env, class, natives.as_ptr(), natives.len() as ::jni::sys::jint)
+                    };
+                    unsafe { delete_local_ref(env, class) };
+                    if status != ::jni::sys::JNI_OK {
+                        ::std::eprintln!(
+                            "Failed to register the native methods of `{}`",
+                            class_name.trim_end_matches('\0')
+                        );
+                        return ::jni::sys::JNI_ERR;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hook::HookOutput;
+
+    #[test]
+    fn test_parse_priority() {
+        for (attr, priority) in [
+            (quote::quote! {}, 0),
+            (quote::quote! { priority = 10 }, 10),
+            (quote::quote! { priority = -5, }, -5),
+            (quote::quote! { priority = 0x10 }, 16),
+        ] {
+            assert_eq!(parse_priority(attr, Registry::Init).unwrap(), priority);
+        }
+
+        for (attr, message) in [
+            (
+                quote::quote! { order = 1 },
+                "Unknown `on_load_init` attribute option `order`; expected `priority`",
+            ),
+            (
+                quote::quote! { priority = 1, priority = 2 },
+                "`priority` may only be specified once",
+            ),
+            (
+                quote::quote! { priority = 3000000000 },
+                "The `priority` must fit in an `i32`",
+            ),
+            (quote::quote! { priority = "1" }, "expected integer literal"),
+        ] {
+            assert_eq!(
+                parse_priority(attr, Registry::Init)
+                    .unwrap_err()
+                    .to_string(),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let target: ItemFn = syn::parse_quote! {
+            #[cfg(feature = "cache")]
+            fn cache_classes(vm: &JavaVM) -> Result<(), Error> {}
+        };
+        let signature = HookSignature {
+            vm: VmParam::Borrowed,
+            options: false,
+            output: HookOutput::UnitResult,
+        };
+        let registered = register(&target, Registry::Init, -1, signature);
+        let convert = hook::vm_conversion(&quote::quote! { false });
+        let expected = quote::quote! {
+            #[cfg(feature = "cache")]
+            const _: () = {
+                fn __jni_registered(__jni_vm: *mut ::jni::sys::JavaVM) -> bool {
+                    #convert
+                    match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| cache_classes(&__jni_vm))) {
+                        ::core::result::Result::Ok(::core::result::Result::Ok(())) => true,
+                        ::core::result::Result::Ok(::core::result::Result::Err(error)) => {
+                            ::std::eprintln!("`cache_classes` failed: {:?}", error);
+                            false
+                        }
+                        ::core::result::Result::Err(payload) => {
+                            let message = payload
+                                .downcast_ref::<&str>()
+                                .copied()
+                                .or_else(|| payload.downcast_ref::<::std::string::String>().map(|message| message.as_str()))
+                                .unwrap_or("Box<dyn Any>");
+                            ::std::eprintln!("`cache_classes` panicked: {}", message);
+                            false
+                        }
+                    }
+                }
+
+                #[used]
+                #[cfg_attr(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "illumos"), unsafe(link_section = "java_native_on_load_init"))]
+                #[cfg_attr(target_vendor = "apple", unsafe(link_section = "__DATA,__jni_init,regular,no_dead_strip"))]
+                #[cfg_attr(windows, unsafe(link_section = ".jni_init$b"))]
+                static __JNI_REGISTERED: (i32, &str, fn(*mut ::jni::sys::JavaVM) -> bool) = (
+                    -1i32,
+                    ::core::concat!(::core::module_path!(), "::", "cache_classes"),
+                    __jni_registered,
+                );
+            };
+        };
+        assert_eq!(registered.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_register_raw() {
+        let target: ItemFn = syn::parse_quote! {
+            unsafe fn set_up(vm: *mut JavaVM, reserved: *mut c_void) {}
+        };
+        let signature = HookSignature {
+            vm: VmParam::Raw,
+            options: false,
+            output: HookOutput::Unit,
+        };
+        let registered = register(&target, Registry::Cleanup, 0, signature);
+        let registered = registered.to_string();
+        assert!(registered.contains(
+            &quote::quote! { unsafe { set_up(__jni_vm as _, ::core::ptr::null_mut()) } }
+                .to_string()
+        ));
+        assert!(!registered.contains("from_raw"));
+        assert!(registered
+            .contains(&quote::quote! { unsafe(link_section = ".jni_cleanup$b") }.to_string()));
+    }
+
+    #[test]
+    fn test_register_native() {
+        let function: ItemFn = syn::parse_quote! {
+            #[cfg(feature = "codec")]
+            extern "system" fn __jni_native_Java_com_example_Codec_encode(__jni_arg0: JNIEnv) {}
+        };
+        let method = NativeMethod {
+            class: "com/example/Codec".to_string(),
+            name: "encode".to_string(),
+            descriptor: "()V".to_string(),
+        };
+        let path = quote::quote! { <Codec>::__jni_native_Java_com_example_Codec_encode };
+        let registered = register_native(&function, &path, &method);
+        let expected = quote::quote! {
+            #[cfg(feature = "codec")]
+            const _: () = {
+                fn __jni_native() -> *mut ::core::ffi::c_void {
+                    <Codec>::__jni_native_Java_com_example_Codec_encode as *mut ::core::ffi::c_void
+                }
+
+                #[used]
+                #[cfg_attr(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "illumos"), unsafe(link_section = "java_native_natives"))]
+                #[cfg_attr(target_vendor = "apple", unsafe(link_section = "__DATA,__jni_natives,regular,no_dead_strip"))]
+                #[cfg_attr(windows, unsafe(link_section = ".jni_natives$b"))]
+                static __JNI_NATIVE: (&str, &str, &str, fn() -> *mut ::core::ffi::c_void) =
+                    ("com/example/Codec\0", "encode\0", "()V\0", __jni_native);
+            };
+        };
+        assert_eq!(registered.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_register_natives() {
+        let natives = register_natives(&quote::quote! { __jni_vm }).to_string();
+        assert!(natives.contains("\"__start_java_native_natives\""));
+        assert!(natives.contains("\".jni_natives$c\""));
+        assert!(natives.contains(
+            &quote::quote! {
+                entries.sort_by_key(|&(class, name, descriptor, _)| (class, name, descriptor));
+            }
+            .to_string()
+        ));
+        assert!(natives.contains(
+            &quote::quote! {
+                for methods in entries.chunk_by(|a, b| a.0 == b.0)
+            }
+            .to_string()
+        ));
+    }
+
+    #[test]
+    fn test_run() {
+        let init = run(Registry::Init, &quote::quote! { __jni_vm }).to_string();
+        assert!(init.contains("\"__start_java_native_on_load_init\""));
+        assert!(init.contains("\"\\u{1}section$end$__DATA$__jni_init\""));
+        assert!(init.contains(
+            &quote::quote! {
+                if !function(vm) {
+                    return ::jni::sys::JNI_ERR;
+                }
+            }
+            .to_string()
+        ));
+
+        let cleanup = run(Registry::Cleanup, &quote::quote! { __jni_vm }).to_string();
+        assert!(cleanup.contains("\"__stop_java_native_on_unload_cleanup\""));
+        assert!(cleanup.contains("\".jni_cleanup$a\""));
+        assert!(!cleanup.contains("JNI_ERR"));
+    }
+}
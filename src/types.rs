@@ -0,0 +1,743 @@
+//! Checks that exported functions only take and return types which can cross the JNI boundary,
+//! and derives the method descriptors of exported functions from these types.
+//!
+//! Types are recognized by the last segment of their path, so they may be imported under any
+//! module path (`jni::sys::jint`, `jni_sys::jint` or just `jint`), but not renamed. Other types
+//! can be allowed with the `type_bound` option, which names a trait that they must implement.
+
+use quote::ToTokens;
+use syn::spanned::Spanned;
+
+use crate::descriptor::{self, FieldType, MethodDescriptor};
+use crate::Errors;
+
+/// Types of the JNI environment, which must be the first parameter.
+const ENV_TYPES: &[&str] = &["JNIEnv"];
+
+/// Types of the class (for static methods) or object (for instance methods) the method is called
+/// on, which must be the second parameter.
+const RECEIVER_TYPES: &[&str] = &["JClass", "JObject", "jclass", "jobject"];
+
+/// Types which have the same representation as a JNI argument or return value: the primitive and
+/// reference types of `jni-sys`, the transparent reference wrappers of `jni`, and the Rust
+/// primitives behind the `jni-sys` type aliases.
+const JNI_TYPES: &[&str] = &[
+    // primitive types
+    "jboolean",
+    "jbyte",
+    "jchar",
+    "jshort",
+    "jint",
+    "jlong",
+    "jfloat",
+    "jdouble",
+    "jsize",
+    "u8",
+    "i8",
+    "u16",
+    "i16",
+    "i32",
+    "i64",
+    "f32",
+    "f64",
+    // raw references
+    "jobject",
+    "jclass",
+    "jstring",
+    "jthrowable",
+    "jweak",
+    "jarray",
+    "jbooleanArray",
+    "jbyteArray",
+    "jcharArray",
+    "jshortArray",
+    "jintArray",
+    "jlongArray",
+    "jfloatArray",
+    "jdoubleArray",
+    "jobjectArray",
+    // `jni` wrappers
+    "JObject",
+    "JClass",
+    "JString",
+    "JThrowable",
+    "JByteBuffer",
+    "JPrimitiveArray",
+    "JBooleanArray",
+    "JByteArray",
+    "JCharArray",
+    "JShortArray",
+    "JIntArray",
+    "JLongArray",
+    "JFloatArray",
+    "JDoubleArray",
+    "JObjectArray",
+];
+
+/// Checks the parameter and return types of a function to be exported. Types which aren't known
+/// to be JNI types are returned if a `type_bound` is given, so that the bound can be asserted on
+/// them; otherwise, they are rejected. Every problem in the signature is reported.
+pub(crate) fn check_signature(
+    sig: &syn::Signature,
+    has_type_bound: bool,
+) -> syn::Result<Vec<syn::Type>> {
+    let mut params = sig.inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(arg) => Some(arg),
+        syn::FnArg::Receiver(_) => None,
+    });
+
+    let mut errors = Errors::default();
+    match params.next() {
+        Some(env) if is_env(&env.ty) => {}
+        other => {
+            errors.push(syn::Error::new(
+                other.map_or(sig.paren_token.span.join(), |env| env.ty.span()),
+                "The first parameter of a `jni_fn` exported function must be the JNI environment (`JNIEnv` or `*mut jni_sys::JNIEnv`)",
+            ));
+        }
+    }
+    match params.next() {
+        Some(receiver) if has_last_segment(&receiver.ty, RECEIVER_TYPES) => {}
+        other => {
+            errors.push(syn::Error::new(
+                other.map_or(sig.paren_token.span.join(), |receiver| receiver.ty.span()),
+                "The second parameter of a `jni_fn` exported function must be the class or object the method is called on (`JClass`, `JObject`, `jclass` or `jobject`)",
+            ));
+        }
+    }
+
+    let mut custom_types = Vec::new();
+    for (i, param) in params.enumerate() {
+        if has_last_segment(&param.ty, JNI_TYPES) {
+            continue;
+        }
+        if has_type_bound {
+            custom_types.push((*param.ty).clone());
+            continue;
+        }
+        let name = param_name(param, i + 2);
+        errors.push(syn::Error::new(
+            param.ty.span(),
+            format!(
+                "Parameter {} has type `{}`, which can't be passed over the JNI; {}",
+                name,
+                display(&param.ty),
+                suggestion(&param.ty)
+            ),
+        ));
+    }
+
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        let is_unit = matches!(&**ty, syn::Type::Tuple(tuple) if tuple.elems.is_empty());
+        if !is_unit && !has_last_segment(ty, JNI_TYPES) {
+            if !has_type_bound {
+                errors.push(syn::Error::new(
+                    ty.span(),
+                    format!(
+                        "The return type `{}` can't be passed over the JNI; {}",
+                        display(ty),
+                        suggestion(ty)
+                    ),
+                ));
+            } else {
+                custom_types.push((**ty).clone());
+            }
+        }
+    }
+
+    errors.finish()?;
+    Ok(custom_types)
+}
+
+/// The attribute which declares the Java type of a parameter, like `#[jtype("java.util.List")]`.
+const JTYPE_ATTRIBUTE: &str = "jtype";
+
+/// Returns the JVM type of a JNI type, or `None` if its Rust type doesn't tell, like for `jarray`
+/// or for types allowed by a `type_bound`.
+fn field_type(ty: &syn::Type) -> Option<FieldType> {
+    let syn::Type::Path(path) = unwrap_type(ty) else {
+        return None;
+    };
+    let segment = path.path.segments.last().filter(|_| path.qself.is_none())?;
+    let exact = |descriptor: &str| Some(FieldType::Exact(descriptor.to_string()));
+    match segment.ident.to_string().as_str() {
+        "jboolean" | "u8" => exact("Z"),
+        "jbyte" | "i8" => exact("B"),
+        "jchar" | "u16" => exact("C"),
+        "jshort" | "i16" => exact("S"),
+        "jint" | "jsize" | "i32" => exact("I"),
+        "jlong" | "i64" => exact("J"),
+        "jfloat" | "f32" => exact("F"),
+        "jdouble" | "f64" => exact("D"),
+        "jstring" | "JString" => exact("Ljava/lang/String;"),
+        "jclass" | "JClass" => exact("Ljava/lang/Class;"),
+        "jbooleanArray" | "JBooleanArray" => exact("[Z"),
+        "jbyteArray" | "JByteArray" => exact("[B"),
+        "jcharArray" | "JCharArray" => exact("[C"),
+        "jshortArray" | "JShortArray" => exact("[S"),
+        "jintArray" | "JIntArray" => exact("[I"),
+        "jlongArray" | "JLongArray" => exact("[J"),
+        "jfloatArray" | "JFloatArray" => exact("[F"),
+        "jdoubleArray" | "JDoubleArray" => exact("[D"),
+        "jobject" | "jweak" | "JObject" => Some(FieldType::Erased {
+            descriptor: "Ljava/lang/Object;",
+            kinds: &["L", "["],
+        }),
+        "jthrowable" | "JThrowable" => Some(FieldType::Erased {
+            descriptor: "Ljava/lang/Throwable;",
+            kinds: &["L"],
+        }),
+        "JByteBuffer" => Some(FieldType::Erased {
+            descriptor: "Ljava/nio/ByteBuffer;",
+            kinds: &["L"],
+        }),
+        "jobjectArray" | "JObjectArray" => Some(FieldType::Erased {
+            descriptor: "[Ljava/lang/Object;",
+            kinds: &["[L", "[["],
+        }),
+        // `JPrimitiveArray<'local, T>`, whose element type is the type argument
+        "JPrimitiveArray" => {
+            let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                return None;
+            };
+            let element = args.args.iter().find_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => field_type(ty),
+                _ => None,
+            })?;
+            let element = element.descriptor();
+            (element.len() == 1).then(|| FieldType::Exact(format!("[{}", element)))
+        }
+        _ => None,
+    }
+}
+
+/// Derives the method descriptor of a function to be exported from its parameter and return types,
+/// where parameters may be given a Java type with `#[jtype("...")]`. If an `explicit` descriptor
+/// was given as `signature`, the types are checked against it instead, and it is returned.
+///
+/// `None` is returned if some type doesn't tell its JVM type, which is an error if the descriptor
+/// is `required`. A required descriptor must also match the Java declaration exactly, so erased
+/// types like `JObject` need a `#[jtype]` then. The JNI environment and the receiver, which must be
+/// the first two parameters, aren't part of the descriptor.
+pub(crate) fn method_descriptor(
+    sig: &syn::Signature,
+    explicit: Option<(&syn::LitStr, &MethodDescriptor)>,
+    required: bool,
+) -> syn::Result<Option<String>> {
+    let mut errors = Errors::default();
+    let params: Vec<&syn::PatType> = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(arg) => Some(arg),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .skip(2)
+        .collect();
+
+    let mut types = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        let name = param_name(param, i + 2);
+        let jtype = errors.check(jtype(param)).flatten();
+        let ty = match (field_type(&param.ty), jtype) {
+            (Some(ty), Some((lit, descriptor))) if !ty.accepts(&descriptor) => {
+                errors.push(syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "Parameter {} has type `{}`, which can't hold a `{}`",
+                        name,
+                        display(&param.ty),
+                        descriptor
+                    ),
+                ));
+                None
+            }
+            (_, Some((_, descriptor))) => Some(FieldType::Exact(descriptor)),
+            (ty, None) => ty,
+        };
+        types.push(ty);
+    }
+    let ret = match &sig.output {
+        syn::ReturnType::Default => Some(FieldType::Exact("V".to_string())),
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Tuple(tuple) if tuple.elems.is_empty() => {
+                Some(FieldType::Exact("V".to_string()))
+            }
+            ty => field_type(ty),
+        },
+    };
+
+    if let Some((lit, explicit)) = explicit {
+        let declared = explicit.arg_types();
+        if declared.len() != params.len() {
+            errors.push(syn::Error::new(
+                lit.span(),
+                format!(
+                    "The `signature` declares {} parameters, but the function takes {} besides the JNI environment and the receiver",
+                    declared.len(),
+                    params.len()
+                ),
+            ));
+        }
+        for (i, ((param, ty), declared)) in params.iter().zip(&types).zip(declared).enumerate() {
+            let Some(ty) = ty.as_ref().filter(|ty| !ty.accepts(declared)) else {
+                continue;
+            };
+            let declared_as = match jtype_attr(param) {
+                Some(_) => format!("is declared as `{}` with `jtype`", ty.descriptor()),
+                None => format!("has type `{}`", display(&param.ty)),
+            };
+            errors.push(syn::Error::new(
+                param.ty.span(),
+                format!(
+                    "Parameter {} {}, but the `signature` declares it as `{}`",
+                    param_name(param, i + 2),
+                    declared_as,
+                    declared
+                ),
+            ));
+        }
+        if let (Some(ret), syn::ReturnType::Type(_, ty)) = (&ret, &sig.output) {
+            if !ret.accepts(explicit.ret) {
+                errors.push(syn::Error::new(
+                    ty.span(),
+                    format!(
+                        "The return type `{}` doesn't match `{}`, the return type declared by the `signature`",
+                        display(ty),
+                        explicit.ret
+                    ),
+                ));
+            }
+        } else if explicit.ret != "V" && ret.is_some() {
+            errors.push(syn::Error::new(
+                sig.ident.span(),
+                format!(
+                    "The function returns nothing, but the `signature` declares the return type `{}`",
+                    explicit.ret
+                ),
+            ));
+        }
+        errors.finish()?;
+        return Ok(Some(format!("({}){}", explicit.args, explicit.ret)));
+    }
+
+    if required {
+        for (i, (param, ty)) in params.iter().zip(&types).enumerate() {
+            if !matches!(ty, Some(FieldType::Exact(_))) && jtype_attr(param).is_none() {
+                errors.push(syn::Error::new(
+                    param.ty.span(),
+                    format!(
+                        "The Java type of parameter {} can't be derived from its type `{}`; annotate it with `#[jtype(\"...\")]`, or pass the method descriptor as `signature`",
+                        param_name(param, i + 2),
+                        display(&param.ty)
+                    ),
+                ));
+            }
+        }
+        if let (None | Some(FieldType::Erased { .. }), syn::ReturnType::Type(_, ty)) =
+            (&ret, &sig.output)
+        {
+            errors.push(syn::Error::new(
+                ty.span(),
+                format!(
+                    "The Java type of the return type `{}` can't be derived; pass the method descriptor as `signature`",
+                    display(ty)
+                ),
+            ));
+        }
+    }
+    errors.finish()?;
+
+    let args: Option<String> = types
+        .iter()
+        .map(|ty| ty.as_ref().map(FieldType::descriptor))
+        .collect();
+    Ok(args
+        .zip(ret)
+        .map(|(args, ret)| format!("({}){}", args, ret.descriptor())))
+}
+
+/// Removes the `jtype` attributes from the parameters of `sig`, which the compiler wouldn't know.
+pub(crate) fn strip_jtype_attrs(sig: &mut syn::Signature) {
+    for input in &mut sig.inputs {
+        if let syn::FnArg::Typed(arg) = input {
+            arg.attrs
+                .retain(|attr| !attr.path().is_ident(JTYPE_ATTRIBUTE));
+        }
+    }
+}
+
+fn jtype_attr(param: &syn::PatType) -> Option<&syn::Attribute> {
+    param
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident(JTYPE_ATTRIBUTE))
+}
+
+/// Returns the Java type given to a parameter with `#[jtype("...")]`, as a field descriptor.
+fn jtype(param: &syn::PatType) -> syn::Result<Option<(syn::LitStr, String)>> {
+    let mut attrs = param
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident(JTYPE_ATTRIBUTE));
+    let Some(attr) = attrs.next() else {
+        return Ok(None);
+    };
+    if let Some(duplicate) = attrs.next() {
+        return Err(syn::Error::new(
+            duplicate.span(),
+            "Only one `jtype` attribute may be applied to a parameter",
+        ));
+    }
+    let lit: syn::LitStr = attr.parse_args().map_err(|_| {
+        syn::Error::new(
+            attr.span(),
+            "Expected a Java type, as in `#[jtype(\"java.util.List\")]`",
+        )
+    })?;
+    let descriptor = descriptor::java_type_descriptor(&lit.value()).map_err(|reason| {
+        syn::Error::new(
+            lit.span(),
+            format!("Invalid Java type supplied to `jtype`: {}", reason),
+        )
+    })?;
+    Ok(Some((lit, descriptor)))
+}
+
+/// Names a parameter in errors, by its name if it has one and otherwise by its position; `index`
+/// counts from 0.
+fn param_name(param: &syn::PatType, index: usize) -> String {
+    match &*param.pat {
+        syn::Pat::Ident(pat) => format!("`{}`", pat.ident),
+        _ => format!("#{}", index + 1),
+    }
+}
+
+/// Returns whether `ty` is `JNIEnv` or a raw `*mut JNIEnv`.
+fn is_env(ty: &syn::Type) -> bool {
+    match unwrap_type(ty) {
+        syn::Type::Ptr(ptr) => ptr.mutability.is_some() && has_last_segment(&ptr.elem, ENV_TYPES),
+        ty => has_last_segment(ty, ENV_TYPES),
+    }
+}
+
+/// Returns whether `ty` is a path type whose last segment is one of `names`.
+fn has_last_segment(ty: &syn::Type, names: &[&str]) -> bool {
+    match unwrap_type(ty) {
+        syn::Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| names.iter().any(|name| segment.ident == name)),
+        _ => false,
+    }
+}
+
+fn unwrap_type(ty: &syn::Type) -> &syn::Type {
+    match ty {
+        syn::Type::Group(group) => unwrap_type(&group.elem),
+        syn::Type::Paren(paren) => unwrap_type(&paren.elem),
+        ty => ty,
+    }
+}
+
+/// Suggests what to use instead of a type which can't be passed over the JNI.
+fn suggestion(ty: &syn::Type) -> &'static str {
+    if has_last_segment(ty, &["bool"]) {
+        "use `jboolean` instead"
+    } else if has_last_segment(ty, &["String", "str"]) || matches!(ty, syn::Type::Reference(_)) {
+        "use a `jni` type like `JString` or `JObject`, or a `jni-sys` type like `jstring`, instead"
+    } else {
+        "use a `jni` or `jni-sys` type like `JObject` or `jint`, or allow other types with `type_bound`"
+    }
+}
+
+/// Formats a type for error messages, without the spaces that `TokenStream` puts between tokens.
+fn display(ty: &syn::Type) -> String {
+    ty.to_token_stream()
+        .to_string()
+        .replace(" :: ", "::")
+        .replace(" <", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+        .replace("& ", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::Span;
+
+    fn check(sig: syn::Signature, has_type_bound: bool) -> Result<usize, String> {
+        check_signature(&sig, has_type_bound)
+            .map(|types| types.len())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_jni_signatures() {
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, s: JString) -> jboolean },
+                false
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! {
+                    fn f<'local>(env: jni::JNIEnv<'local>, this: JObject<'local>, a: jni::sys::jint, b: f64) -> jni::objects::JByteArray<'local>
+                },
+                false
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: *mut jni_sys::JNIEnv, _: jclass, a: jlongArray) },
+                false
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, h: Handle) -> Out },
+                true
+            ),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_invalid_signatures() {
+        assert_eq!(
+            check(syn::parse_quote! { fn f() }, false),
+            Err("The first parameter of a `jni_fn` exported function must be the JNI environment (`JNIEnv` or `*mut jni_sys::JNIEnv`)".to_string())
+        );
+        assert!(check(
+            syn::parse_quote! { fn f(env: &mut JNIEnv, _: JClass) },
+            false
+        )
+        .is_err());
+        assert_eq!(
+            check(syn::parse_quote! { fn f(env: JNIEnv) }, false),
+            Err("The second parameter of a `jni_fn` exported function must be the class or object the method is called on (`JClass`, `JObject`, `jclass` or `jobject`)".to_string())
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, s: String) },
+                false
+            ),
+            Err("Parameter `s` has type `String`, which can't be passed over the JNI; use a `jni` type like `JString` or `JObject`, or a `jni-sys` type like `jstring`, instead".to_string())
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, (a, b): (jint, jint)) },
+                false
+            ),
+            Err("Parameter #3 has type `(jint, jint)`, which can't be passed over the JNI; use a `jni` or `jni-sys` type like `JObject` or `jint`, or allow other types with `type_bound`".to_string())
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass) -> Vec<u8> },
+                false
+            ),
+            Err("The return type `Vec<u8>` can't be passed over the JNI; use a `jni` or `jni-sys` type like `JObject` or `jint`, or allow other types with `type_bound`".to_string())
+        );
+        assert_eq!(
+            check(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass) -> bool },
+                false
+            ),
+            Err(
+                "The return type `bool` can't be passed over the JNI; use `jboolean` instead"
+                    .to_string()
+            )
+        );
+    }
+
+    fn descriptor(sig: syn::Signature, signature: Option<&str>) -> Result<Option<String>, String> {
+        let lit = signature.map(|signature| syn::LitStr::new(signature, Span::call_site()));
+        let explicit =
+            signature.map(|signature| descriptor::parse_method_descriptor(signature).unwrap());
+        method_descriptor(&sig, lit.as_ref().zip(explicit.as_ref()), false)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_method_descriptor() {
+        for (sig, expected) in [
+            (
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, s: JString, data: JByteArray, b: jboolean) -> jlong },
+                "(Ljava/lang/String;[BZ)J",
+            ),
+            (
+                syn::parse_quote! { fn f(env: JNIEnv, _: JObject, a: i32, o: jni::objects::JObject<'local>) },
+                "(ILjava/lang/Object;)V",
+            ),
+            (
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, a: JPrimitiveArray<'local, jint>) -> JObjectArray },
+                "([I)[Ljava/lang/Object;",
+            ),
+            (
+                syn::parse_quote! {
+                    fn f(env: JNIEnv, _: JClass, #[jtype("java.util.List")] list: JObject, #[jtype("long")] handle: Handle) -> ()
+                },
+                "(Ljava/util/List;J)V",
+            ),
+        ] {
+            assert_eq!(descriptor(sig, None), Ok(Some(expected.to_string())));
+        }
+
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, h: Handle) },
+                None
+            ),
+            Ok(None)
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass) -> jarray },
+                None
+            ),
+            Ok(None)
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, #[jtype("int")] s: JString) },
+                None
+            ),
+            Err("Parameter `s` has type `JString`, which can't hold a `I`".to_string())
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, #[jtype("java.util.List<String>")] l: JObject) },
+                None
+            ),
+            Err("Invalid Java type supplied to `jtype`: type arguments aren't part of descriptors; write `java.util.List`".to_string())
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, #[jtype = "int"] a: Handle) },
+                None
+            ),
+            Err("Expected a Java type, as in `#[jtype(\"java.util.List\")]`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_explicit_method_descriptor() {
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, l: JObject, h: Handle) -> JString },
+                Some("(Ljava/util/List;J)Ljava/lang/String;")
+            ),
+            Ok(Some("(Ljava/util/List;J)Ljava/lang/String;".to_string()))
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, a: jint) },
+                Some("(II)V")
+            ),
+            Err("The `signature` declares 2 parameters, but the function takes 1 besides the JNI environment and the receiver".to_string())
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, s: JString) },
+                Some("(I)V")
+            ),
+            Err(
+                "Parameter `s` has type `JString`, but the `signature` declares it as `I`"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass, #[jtype("java.util.List")] l: JObject) },
+                Some("(Ljava/util/Map;)V")
+            ),
+            Err("Parameter `l` is declared as `Ljava/util/List;` with `jtype`, but the `signature` declares it as `Ljava/util/Map;`".to_string())
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass) -> jint },
+                Some("()J")
+            ),
+            Err("The return type `jint` doesn't match `J`, the return type declared by the `signature`".to_string())
+        );
+        assert_eq!(
+            descriptor(
+                syn::parse_quote! { fn f(env: JNIEnv, _: JClass) },
+                Some("()Z")
+            ),
+            Err(
+                "The function returns nothing, but the `signature` declares the return type `Z`"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_required_method_descriptor() {
+        let sig: syn::Signature =
+            syn::parse_quote! { fn f(env: JNIEnv, _: JClass, (a, b): Pair) -> Handle };
+        let errors: Vec<String> = method_descriptor(&sig, None, true)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "The Java type of parameter #3 can't be derived from its type `Pair`; annotate it with `#[jtype(\"...\")]`, or pass the method descriptor as `signature`",
+                "The Java type of the return type `Handle` can't be derived; pass the method descriptor as `signature`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_required_erased_types() {
+        let sig: syn::Signature = syn::parse_quote! {
+            fn f(env: JNIEnv, _: JClass, a: JObject, #[jtype("int[]")] b: JObject, c: JThrowable) -> JObjectArray
+        };
+        let errors: Vec<String> = method_descriptor(&sig, None, true)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "The Java type of parameter `a` can't be derived from its type `JObject`; annotate it with `#[jtype(\"...\")]`, or pass the method descriptor as `signature`",
+                "The Java type of parameter `c` can't be derived from its type `JThrowable`; annotate it with `#[jtype(\"...\")]`, or pass the method descriptor as `signature`",
+                "The Java type of the return type `JObjectArray` can't be derived; pass the method descriptor as `signature`",
+            ]
+        );
+
+        let sig: syn::Signature = syn::parse_quote! {
+            fn f(env: JNIEnv, _: JClass, #[jtype("java.lang.Object")] a: JObject, b: jint) -> JString
+        };
+        assert_eq!(
+            method_descriptor(&sig, None, true).unwrap().as_deref(),
+            Some("(Ljava/lang/Object;I)Ljava/lang/String;")
+        );
+    }
+
+    #[test]
+    fn test_all_errors_reported() {
+        let sig: syn::Signature =
+            syn::parse_quote! { fn f(env: JNIEnv, this: String, b: Box<u8>) -> bool };
+        let errors: Vec<String> = check_signature(&sig, false)
+            .err()
+            .into_iter()
+            .flatten()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("The second parameter"));
+        assert!(errors[1].starts_with("Parameter `b` has type `Box<u8>`"));
+        assert!(errors[2].starts_with("The return type `bool`"));
+    }
+}